@@ -1,4 +1,11 @@
 #include "esp_afe_sr_models.h"
+#include "esp_afe_config.h"
+#include "model_path.h"
+#include "esp_wn_iface.h"
+#include "esp_wn_models.h"
+#include "esp_mn_iface.h"
+#include "esp_mn_models.h"
+#include "esp_mn_speech_commands.h"
 #include "esp_tts.h"
 #include "esp_tts_voice_template.h"
 #include "esp_tts_voice_xiaole.h"
@@ -0,0 +1,351 @@
+// IMA / Microsoft ADPCM 解码
+// 每次解码一个 WAV block，输出交错排列的 i16 采样
+
+const IMA_INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66,
+    73, 80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449,
+    494, 544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272,
+    2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493,
+    10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const MS_ADAPTATION_TABLE: [i32; 16] = [
+    230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230,
+];
+// 自适应表的最大值为 768
+const MS_MAX_DELTA: i32 = i32::MAX / 768;
+
+// MS ADPCM 标准预测系数，fmt chunk 中未给出时使用
+pub const MS_DEFAULT_COEFS: [(i16, i16); 7] = [
+    (256, 0),
+    (512, -256),
+    (0, 0),
+    (192, 64),
+    (240, 0),
+    (460, -208),
+    (392, -232),
+];
+
+fn clamp16(v: i32) -> i32 {
+    v.clamp(i16::MIN as i32, i16::MAX as i32)
+}
+
+struct ImaState {
+    predictor: i32,
+    index: i32,
+}
+
+impl ImaState {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+        if nibble & 8 != 0 {
+            self.predictor = clamp16(self.predictor - diff);
+        } else {
+            self.predictor = clamp16(self.predictor + diff);
+        }
+        self.index = (self.index + IMA_INDEX_TABLE[(nibble & 7) as usize]).clamp(0, 88);
+        self.predictor as i16
+    }
+}
+
+// 解码一个 IMA ADPCM block
+// 每个声道 4 字节头（初始采样 + step index），之后每个声道交替 4 字节（8 个采样）
+pub fn decode_ima_block(block: &[u8], channels: usize, out: &mut Vec<i16>) {
+    if channels == 0 || block.len() < 4 * channels {
+        return;
+    }
+
+    let mut states: Vec<ImaState> = (0..channels)
+        .map(|ch| {
+            let h = &block[ch * 4..ch * 4 + 4];
+            ImaState {
+                predictor: i16::from_le_bytes([h[0], h[1]]) as i32,
+                index: (h[2] as i32).clamp(0, 88),
+            }
+        })
+        .collect();
+    for state in states.iter() {
+        out.push(state.predictor as i16);
+    }
+
+    let data = &block[4 * channels..];
+    let group = 4 * channels;
+    let mut samples = vec![0i16; 8 * channels];
+    for chunk in data.chunks_exact(group) {
+        for (ch, state) in states.iter_mut().enumerate() {
+            let bytes = &chunk[ch * 4..ch * 4 + 4];
+            for (i, byte) in bytes.iter().enumerate() {
+                // 低 4 位在前
+                samples[(i * 2) * channels + ch] = state.decode(byte & 0x0F);
+                samples[(i * 2 + 1) * channels + ch] = state.decode(byte >> 4);
+            }
+        }
+        out.extend_from_slice(&samples);
+    }
+}
+
+struct MsState {
+    coef1: i32,
+    coef2: i32,
+    delta: i32,
+    sample1: i32,
+    sample2: i32,
+}
+
+impl MsState {
+    fn decode(&mut self, nibble: u8) -> i16 {
+        // 4 位有符号数
+        let signed = ((nibble as i8) << 4 >> 4) as i64;
+        // 文件中的系数和 delta 不可信，用 i64 计算避免溢出
+        let predictor = (self.sample1 as i64 * self.coef1 as i64
+            + self.sample2 as i64 * self.coef2 as i64)
+            >> 8;
+        let sample =
+            (predictor + signed * self.delta as i64).clamp(i16::MIN as i64, i16::MAX as i64);
+
+        self.sample2 = self.sample1;
+        self.sample1 = sample as i32;
+        // 与 ffmpeg 相同，限制 delta 使下一次相乘不溢出
+        self.delta =
+            ((MS_ADAPTATION_TABLE[nibble as usize] * self.delta) >> 8).clamp(16, MS_MAX_DELTA);
+        sample as i16
+    }
+}
+
+// 解码一个 MS ADPCM block
+// 块头依次为每个声道的 predictor 序号、delta、sample1、sample2，之后高 4 位在前交替各声道
+pub fn decode_ms_block(block: &[u8], channels: usize, coefs: &[(i16, i16)], out: &mut Vec<i16>) {
+    let header_len = 7 * channels;
+    if channels == 0 || block.len() < header_len || coefs.is_empty() {
+        return;
+    }
+
+    let read_i16 = |offset: usize| i16::from_le_bytes([block[offset], block[offset + 1]]) as i32;
+    let mut states: Vec<MsState> = (0..channels)
+        .map(|ch| {
+            let (coef1, coef2) = coefs[(block[ch] as usize).min(coefs.len() - 1)];
+            MsState {
+                coef1: coef1 as i32,
+                coef2: coef2 as i32,
+                delta: read_i16(channels + ch * 2),
+                sample1: read_i16(3 * channels + ch * 2),
+                sample2: read_i16(5 * channels + ch * 2),
+            }
+        })
+        .collect();
+
+    // 块头中的两个采样先输出 sample2 再输出 sample1
+    for state in states.iter() {
+        out.push(state.sample2 as i16);
+    }
+    for state in states.iter() {
+        out.push(state.sample1 as i16);
+    }
+
+    let mut ch = 0;
+    for byte in &block[header_len..] {
+        for nibble in [byte >> 4, byte & 0x0F] {
+            out.push(states[ch].decode(nibble));
+            ch = (ch + 1) % channels;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 16000.0;
+
+    // 交错排列的正弦波，各声道频率不同
+    fn sine(frames: usize, channels: usize) -> Vec<i16> {
+        (0..frames)
+            .flat_map(|i| {
+                (0..channels).map(move |ch| {
+                    let freq = 440.0 * (ch + 1) as f32;
+                    let t = i as f32 / SAMPLE_RATE;
+                    (8000.0 * (2.0 * std::f32::consts::PI * freq * t).sin()) as i16
+                })
+            })
+            .collect()
+    }
+
+    fn snr_db(reference: &[i16], decoded: &[i16]) -> f64 {
+        let signal: f64 = reference.iter().map(|&x| (x as f64).powi(2)).sum();
+        let noise: f64 = reference
+            .iter()
+            .zip(decoded)
+            .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+            .sum();
+        10.0 * (signal / noise).log10()
+    }
+
+    // 逐个采样选择误差最小的 nibble
+    fn ima_nibble(state: &mut ImaState, target: i16) -> u8 {
+        let nibble = (0..16u8)
+            .min_by_key(|&n| {
+                let mut trial = ImaState {
+                    predictor: state.predictor,
+                    index: state.index,
+                };
+                (trial.decode(n) as i32 - target as i32).abs()
+            })
+            .unwrap();
+        state.decode(nibble);
+        nibble
+    }
+
+    fn ms_nibble(state: &mut MsState, target: i16) -> u8 {
+        let nibble = (0..16u8)
+            .min_by_key(|&n| {
+                let mut trial = MsState { ..*state };
+                (trial.decode(n) as i32 - target as i32).abs()
+            })
+            .unwrap();
+        state.decode(nibble);
+        nibble
+    }
+
+    // 每个 block 含 1 + 8n 帧
+    fn encode_ima(pcm: &[i16], channels: usize, frames_per_block: usize) -> Vec<Vec<u8>> {
+        let mut indexes = vec![0; channels];
+        pcm.chunks_exact(frames_per_block * channels)
+            .map(|frames| {
+                let mut block = Vec::new();
+                let mut states: Vec<ImaState> = (0..channels)
+                    .map(|ch| ImaState {
+                        predictor: frames[ch] as i32,
+                        index: indexes[ch],
+                    })
+                    .collect();
+                for state in &states {
+                    block.extend_from_slice(&(state.predictor as i16).to_le_bytes());
+                    block.extend_from_slice(&[state.index as u8, 0]);
+                }
+                for group in frames[channels..].chunks_exact(8 * channels) {
+                    for (ch, state) in states.iter_mut().enumerate() {
+                        for pair in 0..4 {
+                            let low = ima_nibble(state, group[pair * 2 * channels + ch]);
+                            let high = ima_nibble(state, group[(pair * 2 + 1) * channels + ch]);
+                            block.push(low | high << 4);
+                        }
+                    }
+                }
+                for (ch, state) in states.iter().enumerate() {
+                    indexes[ch] = state.index;
+                }
+                block
+            })
+            .collect()
+    }
+
+    // 使用第一组预测系数，每个 block 含 2 + 偶数帧
+    fn encode_ms(pcm: &[i16], channels: usize, frames_per_block: usize) -> Vec<Vec<u8>> {
+        let (coef1, coef2) = MS_DEFAULT_COEFS[0];
+        pcm.chunks_exact(frames_per_block * channels)
+            .map(|frames| {
+                let mut states: Vec<MsState> = (0..channels)
+                    .map(|ch| MsState {
+                        coef1: coef1 as i32,
+                        coef2: coef2 as i32,
+                        delta: 16,
+                        sample1: frames[channels + ch] as i32,
+                        sample2: frames[ch] as i32,
+                    })
+                    .collect();
+                let mut block = vec![0u8; channels];
+                for state in &states {
+                    block.extend_from_slice(&(state.delta as i16).to_le_bytes());
+                }
+                for state in &states {
+                    block.extend_from_slice(&(state.sample1 as i16).to_le_bytes());
+                }
+                for state in &states {
+                    block.extend_from_slice(&(state.sample2 as i16).to_le_bytes());
+                }
+                let nibbles: Vec<u8> = frames[2 * channels..]
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &sample)| ms_nibble(&mut states[i % channels], sample))
+                    .collect();
+                block.extend(nibbles.chunks_exact(2).map(|n| n[0] << 4 | n[1]));
+                block
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ima_round_trip() {
+        for channels in [1, 2] {
+            let frames_per_block = 1 + 8 * 63;
+            let pcm = sine(frames_per_block * 4, channels);
+            let blocks = encode_ima(&pcm, channels, frames_per_block);
+            assert_eq!(blocks[0].len(), 256 * channels);
+
+            let mut out = Vec::new();
+            for block in &blocks {
+                decode_ima_block(block, channels, &mut out);
+            }
+            assert_eq!(out.len(), pcm.len());
+            let snr = snr_db(&pcm, &out);
+            assert!(snr > 20.0, "ima {} channels snr {:.1} dB", channels, snr);
+        }
+    }
+
+    #[test]
+    fn ms_round_trip() {
+        for channels in [1, 2] {
+            let frames_per_block = 2 + 2 * 250;
+            let pcm = sine(frames_per_block * 4, channels);
+            let blocks = encode_ms(&pcm, channels, frames_per_block);
+            assert_eq!(blocks[0].len(), 7 * channels + 250 * channels);
+
+            let mut out = Vec::new();
+            for block in &blocks {
+                decode_ms_block(block, channels, &MS_DEFAULT_COEFS, &mut out);
+            }
+            assert_eq!(out.len(), pcm.len());
+            let snr = snr_db(&pcm, &out);
+            assert!(snr > 20.0, "ms {} channels snr {:.1} dB", channels, snr);
+        }
+    }
+
+    // 恶意文件：delta 为 0x7fff，之后每个 nibble 都使 delta 增大
+    #[test]
+    fn ms_hostile_block() {
+        let mut block = vec![0u8, 0xff, 0x7f, 0, 0, 0, 0];
+        block.extend([0x88; 16]);
+        let mut out = Vec::new();
+        decode_ms_block(&block, 1, &MS_DEFAULT_COEFS, &mut out);
+        assert_eq!(out.len(), 2 + 32);
+
+        // 自定义系数为 -32768，历史采样为 -32768
+        let mut block = vec![0u8, 0x10, 0, 0, 0x80, 0, 0x80];
+        block.extend([0x77, 0x88, 0xff, 0x00]);
+        let mut out = Vec::new();
+        decode_ms_block(&block, 1, &[(i16::MIN, i16::MIN)], &mut out);
+        assert_eq!(out.len(), 2 + 8);
+        assert_eq!(&out[..2], &[i16::MIN, i16::MIN]);
+    }
+
+    #[test]
+    fn short_block() {
+        let mut out = Vec::new();
+        decode_ima_block(&[0; 3], 1, &mut out);
+        decode_ms_block(&[0; 13], 2, &MS_DEFAULT_COEFS, &mut out);
+        decode_ms_block(&[0; 7], 1, &[], &mut out);
+        assert!(out.is_empty());
+    }
+}
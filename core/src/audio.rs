@@ -0,0 +1,246 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::event::{self, Event};
+use crate::global;
+use crate::mixer::{self, Mixer};
+use crate::sink::{self, AudioSink};
+use crate::telemetry::Telemetry;
+
+// 发送到音频线程的数据包，均携带播报 ID
+#[derive(Debug)]
+pub enum Packet {
+    Begin(u32),
+    Data(u32, Vec<i16>),
+    End(u32),
+}
+
+// 音频队列中的数据包数量
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+static TELEMETRY: Mutex<Option<Telemetry>> = Mutex::new(None);
+// 正在播放播报
+static SPEAKING: AtomicBool = AtomicBool::new(false);
+
+// 扬声器输出的订阅者及各自丢弃的帧数，例如 AEC 回声消除的参考信号、音频监听
+type OutputSubscriber = (mpsc::SyncSender<Vec<i16>>, Arc<AtomicUsize>);
+static OUTPUT_SUBSCRIBERS: Mutex<Vec<OutputSubscriber>> = Mutex::new(Vec::new());
+
+pub struct Tap {
+    pub rx: mpsc::Receiver<Vec<i16>>,
+    pub dropped: Arc<AtomicUsize>,
+}
+
+pub struct Audio {
+    sink: Box<dyn AudioSink>,
+    // 播报是否已被停止，由播报队列提供
+    is_stopped: fn(u32) -> bool,
+    // 上一次播报结束的时间，用于保持播报间隔
+    last_end: Option<Instant>,
+}
+
+impl Audio {
+    pub fn new(sink: Box<dyn AudioSink>, is_stopped: fn(u32) -> bool) -> Self {
+        Audio {
+            sink,
+            is_stopped,
+            last_end: None,
+        }
+    }
+
+    // 与上一次播报之间补足固定间隔的静音
+    fn insert_gap(&mut self) {
+        let gap_ms = global::SILENCE.get().unwrap().lock().unwrap().gap_ms as u64;
+        let Some(last_end) = self.last_end else {
+            return;
+        };
+
+        let mut mixer = mixer();
+        // 上一次播报还在缓冲区中时需要完整的间隔
+        let remaining = if mixer.buffered(mixer::SPEECH_ID) > 0 {
+            gap_ms
+        } else {
+            gap_ms.saturating_sub(last_end.elapsed().as_millis() as u64)
+        };
+        if remaining > 0 {
+            let samples = (global::SAMPLE_RATE as u64 * remaining / 1000) as usize;
+            mixer.push(mixer::SPEECH_ID, &vec![0; samples]);
+        }
+    }
+
+    // 语音数据写入混音器的语音音源，current 记录正在播放的播报 ID
+    fn handle_packet(&mut self, packet: Packet, current: &mut Option<u32>) {
+        match packet {
+            Packet::Begin(id) => {
+                *current = Some(id);
+                if !(self.is_stopped)(id) {
+                    SPEAKING.store(true, Ordering::Relaxed);
+                    mixer().set_ducking(true);
+                    self.insert_gap();
+                    event::emit(Event::Started { id });
+                }
+            }
+            Packet::Data(id, pcm) => {
+                // 已停止的播报直接丢弃
+                if !(self.is_stopped)(id) {
+                    mixer().push(mixer::SPEECH_ID, &pcm);
+                }
+            }
+            Packet::End(id) => {
+                *current = None;
+                SPEAKING.store(false, Ordering::Relaxed);
+                mixer().set_ducking(false);
+                self.last_end = Some(Instant::now());
+                if (self.is_stopped)(id) {
+                    event::emit(Event::Stopped { id });
+                } else {
+                    event::emit(Event::Finished { id });
+                }
+            }
+        }
+    }
+
+    pub fn play_with_tx(&mut self, tx: mpsc::Receiver<Packet>) {
+        let mut frame = vec![0i16; global::MIXER_FRAME_SAMPLES];
+        let mut current = None;
+
+        loop {
+            // 语音缓冲不足一帧时读取数据包，混音器空闲时等待数据包或新加入的背景音源
+            while mixer().buffered(mixer::SPEECH_ID) < frame.len() {
+                let packet = if mixer().is_active() {
+                    match tx.try_recv() {
+                        Ok(packet) => packet,
+                        Err(_) => {
+                            // 播报进行中但语音数据未送达
+                            if current.is_some_and(|id| !(self.is_stopped)(id)) {
+                                telemetry(|t| t.underruns += 1);
+                            }
+                            break;
+                        }
+                    }
+                } else {
+                    telemetry(|t| t.record_idle());
+                    match tx.recv_timeout(Duration::from_millis(global::MIXER_IDLE_POLL_MS)) {
+                        Ok(packet) => packet,
+                        Err(mpsc::RecvTimeoutError::Timeout) => break,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                    }
+                };
+                let depth = QUEUED.fetch_sub(1, Ordering::Relaxed).saturating_sub(1);
+                telemetry(|t| t.record_queue_depth(depth));
+                self.handle_packet(packet, &mut current);
+            }
+
+            if current.is_some_and(self.is_stopped) {
+                mixer().clear(mixer::SPEECH_ID);
+            }
+
+            let gain = *global::PLAY_GAIN.get().unwrap().lock().unwrap();
+            let clipped = {
+                let mut mixer = mixer();
+                if !mixer.is_active() {
+                    continue;
+                }
+                mixer.mix(&mut frame, gain as f32)
+            };
+            telemetry(|t| t.record_frame(&frame, clipped));
+            publish_output(&frame);
+            if let Err(e) = self.sink.write(&frame) {
+                if e.is::<sink::Timeout>() {
+                    telemetry(|t| t.write_timeouts += 1);
+                } else {
+                    telemetry(|t| t.write_errors += 1);
+                }
+                log::warn!("audio sink write error: {:?}", e);
+            }
+        }
+    }
+}
+
+// 发送数据包到音频线程并记录队列深度
+pub fn send(tx: &mpsc::SyncSender<Packet>, packet: Packet) {
+    QUEUED.fetch_add(1, Ordering::Relaxed);
+    if tx.send(packet).is_err() {
+        QUEUED.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn telemetry(f: impl FnOnce(&mut Telemetry)) {
+    f(TELEMETRY
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Telemetry::default));
+}
+
+pub fn is_speaking() -> bool {
+    SPEAKING.load(Ordering::Relaxed)
+}
+
+// 播放统计的快照
+pub fn stats() -> Telemetry {
+    TELEMETRY.lock().unwrap().clone().unwrap_or_default()
+}
+
+// 订阅写入 sink 的音频，返回的 Receiver 被 drop 后自动取消订阅
+pub fn subscribe_output() -> mpsc::Receiver<Vec<i16>> {
+    tap(global::OUTPUT_QUEUE_LEN).rx
+}
+
+// 带丢帧计数的订阅，queue_len 为该订阅者最多缓存的帧数
+pub fn tap(queue_len: usize) -> Tap {
+    let (tx, rx) = mpsc::sync_channel(queue_len);
+    let dropped = Arc::new(AtomicUsize::new(0));
+    OUTPUT_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .push((tx, dropped.clone()));
+    Tap { rx, dropped }
+}
+
+fn publish_output(frame: &[i16]) {
+    let mut subscribers = OUTPUT_SUBSCRIBERS.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    subscribers.retain(|(tx, dropped)| match tx.try_send(frame.to_vec()) {
+        Ok(()) => true,
+        // 订阅者处理不过来时丢帧，不阻塞音频线程
+        Err(mpsc::TrySendError::Full(_)) => {
+            dropped.fetch_add(1, Ordering::Relaxed);
+            true
+        }
+        Err(mpsc::TrySendError::Disconnected(_)) => false,
+    });
+}
+
+pub fn mixer() -> MutexGuard<'static, Mixer> {
+    global::MIXER.get().unwrap().lock().unwrap()
+}
+
+pub fn volume() -> u8 {
+    *global::PLAY_GAIN.get().unwrap().lock().unwrap()
+}
+
+// 设置主音量 0 ~ 100，变化时发出 Volume 事件
+pub fn set_volume(level: u8) {
+    let level = level.min(100);
+    let changed = {
+        let mut gain = global::PLAY_GAIN.get().unwrap().lock().unwrap();
+        let changed = *gain != level;
+        *gain = level;
+        changed
+    };
+    if changed {
+        event::emit(Event::Volume { level });
+    }
+}
+
+pub fn volume_up() {
+    log::info!("volume_up");
+    set_volume(volume().saturating_add(1));
+}
+
+pub fn volume_down() {
+    log::info!("volume_down");
+    set_volume(volume().saturating_sub(1));
+}
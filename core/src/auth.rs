@@ -0,0 +1,474 @@
+// 访问认证：Bearer token 与 HTTP Basic 用户，凭据只保存加盐的 PBKDF2-SHA256 哈希
+//
+// 没有任何凭据时不启用认证，所有请求按 admin 处理
+// speak 权限：播报、停止、音量、查看状态等；admin 权限包含 speak，另可修改配置和管理凭据
+
+use std::fmt;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+// 凭据名称的最大长度
+pub const NAME_MAX_LEN: usize = 32;
+// 密码的最小长度
+pub const PASSWORD_MIN_LEN: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    Speak,
+    Admin,
+}
+
+impl Scope {
+    // admin 包含 speak 的全部权限
+    pub fn allows(self, required: Scope) -> bool {
+        self >= required
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Speak => write!(f, "speak"),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    // Authorization: Bearer <token>
+    Token,
+    // Authorization: Basic base64(name:password)
+    Password,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credential {
+    pub name: String,
+    pub kind: Kind,
+    pub scope: Scope,
+    // 十六进制编码
+    salt: String,
+    hash: String,
+    rounds: u32,
+}
+
+// 列出凭据时返回的信息，不含哈希
+#[derive(Debug, Clone, Serialize)]
+pub struct Info {
+    pub name: String,
+    pub kind: Kind,
+    pub scope: Scope,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Denied {
+    // 未提供凭据 (401)
+    Missing,
+    // 凭据错误 (401)
+    Invalid,
+    // 权限不足 (403)
+    Forbidden,
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Denied::Missing => write!(f, "authentication required"),
+            Denied::Invalid => write!(f, "invalid credentials"),
+            Denied::Forbidden => write!(f, "insufficient scope"),
+        }
+    }
+}
+
+impl std::error::Error for Denied {}
+
+// 凭据修改违反规则，例如删除最后一个 admin 凭据
+#[derive(Debug)]
+pub struct Rejected(pub String);
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+impl Credential {
+    pub fn new(
+        name: &str,
+        kind: Kind,
+        scope: Scope,
+        secret: &str,
+        salt: &[u8],
+        rounds: u32,
+    ) -> Self {
+        Credential {
+            name: name.to_string(),
+            kind,
+            scope,
+            salt: hex(salt),
+            hash: hex(&derive(secret, salt, rounds)),
+            rounds,
+        }
+    }
+
+    fn verify(&self, secret: &str) -> bool {
+        let Some(salt) = unhex(&self.salt) else {
+            return false;
+        };
+        let hash = hex(&derive(secret, &salt, self.rounds));
+        constant_time_eq(hash.as_bytes(), self.hash.as_bytes())
+    }
+
+    pub fn info(&self) -> Info {
+        Info {
+            name: self.name.clone(),
+            kind: self.kind,
+            scope: self.scope,
+        }
+    }
+}
+
+// 保存到 NVS 的全部凭据
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Store {
+    credentials: Vec<Credential>,
+}
+
+impl Store {
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    pub fn list(&self) -> Vec<Info> {
+        self.credentials.iter().map(Credential::info).collect()
+    }
+
+    // 同名凭据被替换，可用于修改密码或重新生成 token
+    pub fn add(&mut self, credential: Credential, max_len: usize) -> Result<(), Rejected> {
+        validate_name(&credential.name).map_err(|e| Rejected(e.to_string()))?;
+        let mut credentials = self.credentials.clone();
+        credentials.retain(|c| c.name != credential.name);
+        if credentials.len() >= max_len {
+            return Err(Rejected(format!("too many credentials (max {})", max_len)));
+        }
+        credentials.push(credential);
+        check_admin(&credentials)?;
+        self.credentials = credentials;
+        Ok(())
+    }
+
+    // 不存在时返回 false，删除全部凭据后认证关闭
+    pub fn remove(&mut self, name: &str) -> Result<bool, Rejected> {
+        let mut credentials = self.credentials.clone();
+        credentials.retain(|c| c.name != name);
+        if credentials.len() == self.credentials.len() {
+            return Ok(false);
+        }
+        check_admin(&credentials)?;
+        self.credentials = credentials;
+        Ok(true)
+    }
+
+    // 按 Authorization 请求头确定权限
+    pub fn authenticate(&self, authorization: Option<&str>) -> Result<Scope, Denied> {
+        if !self.is_enabled() {
+            return Ok(Scope::Admin);
+        }
+        let authorization = authorization.ok_or(Denied::Missing)?;
+        let (scheme, value) = authorization
+            .trim()
+            .split_once(' ')
+            .ok_or(Denied::Invalid)?;
+        let value = value.trim();
+
+        let found = if scheme.eq_ignore_ascii_case("bearer") {
+            self.credentials
+                .iter()
+                .filter(|c| c.kind == Kind::Token)
+                .find(|c| c.verify(value))
+        } else if scheme.eq_ignore_ascii_case("basic") {
+            let decoded = base64::engine::general_purpose::STANDARD
+                .decode(value)
+                .map_err(|_| Denied::Invalid)?;
+            let decoded = String::from_utf8(decoded).map_err(|_| Denied::Invalid)?;
+            let (name, password) = decoded.split_once(':').ok_or(Denied::Invalid)?;
+            self.credentials
+                .iter()
+                .find(|c| c.kind == Kind::Password && c.name == name)
+                .filter(|c| c.verify(password))
+        } else {
+            None
+        };
+        found.map(|c| c.scope).ok_or(Denied::Invalid)
+    }
+
+    pub fn authorize(&self, authorization: Option<&str>, required: Scope) -> Result<Scope, Denied> {
+        let scope = self.authenticate(authorization)?;
+        if !scope.allows(required) {
+            return Err(Denied::Forbidden);
+        }
+        Ok(scope)
+    }
+}
+
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > NAME_MAX_LEN {
+        anyhow::bail!("name must be 1 ~ {} characters", NAME_MAX_LEN);
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        anyhow::bail!("invalid name: {}", name);
+    }
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> anyhow::Result<()> {
+    if password.chars().count() < PASSWORD_MIN_LEN {
+        anyhow::bail!("password must be at least {} characters", PASSWORD_MIN_LEN);
+    }
+    Ok(())
+}
+
+// 启用认证时必须保留至少一个 admin 凭据，避免无法再修改配置
+fn check_admin(credentials: &[Credential]) -> Result<(), Rejected> {
+    if !credentials.is_empty() && !credentials.iter().any(|c| c.scope == Scope::Admin) {
+        return Err(Rejected(
+            "at least one admin credential is required".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn derive(secret: &str, salt: &[u8], rounds: u32) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(secret.as_bytes(), salt, rounds.max(1), &mut out);
+    out
+}
+
+// 比较耗时与内容无关
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| match pair {
+            [hi, lo] => u8::from_str_radix(std::str::from_utf8(&[*hi, *lo]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn basic(name: &str, password: &str) -> String {
+        let encoded =
+            base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", name, password));
+        format!("Basic {}", encoded)
+    }
+
+    fn store() -> Store {
+        let mut store = Store::default();
+        let admin = Credential::new(
+            "admin",
+            Kind::Password,
+            Scope::Admin,
+            "change-me",
+            b"salt1",
+            100,
+        );
+        store.add(admin, 4).unwrap();
+        let tv = Credential::new("tv", Kind::Token, Scope::Speak, "abc123", b"salt2", 1);
+        store.add(tv, 4).unwrap();
+        store
+    }
+
+    #[test]
+    fn pbkdf2_verify() {
+        let credential = Credential::new("a", Kind::Password, Scope::Admin, "secret", b"salt", 10);
+        assert!(credential.verify("secret"));
+        assert!(!credential.verify("Secret"));
+        assert!(!credential.verify(""));
+
+        // RFC 7914 第 11 节的 PBKDF2-HMAC-SHA256 测试向量
+        let mut out = [0u8; 64];
+        pbkdf2::pbkdf2_hmac::<Sha256>(b"passwd", b"salt", 1, &mut out);
+        assert_eq!(
+            &hex(&out)[..64],
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+        assert_eq!(hex(&derive("passwd", b"salt", 1)), &hex(&out)[..64]);
+
+        // 盐和轮数都参与计算
+        let other_salt = Credential::new("a", Kind::Password, Scope::Admin, "secret", b"SALT", 10);
+        let other_rounds =
+            Credential::new("a", Kind::Password, Scope::Admin, "secret", b"salt", 11);
+        assert_ne!(credential.hash, other_salt.hash);
+        assert_ne!(credential.hash, other_rounds.hash);
+
+        // 保存后再加载仍可校验
+        let json = serde_json::to_string(&credential).unwrap();
+        assert!(!json.contains("secret"));
+        let loaded: Credential = serde_json::from_str(&json).unwrap();
+        assert!(loaded.verify("secret"));
+
+        let mut broken = credential.clone();
+        broken.salt = "zz".to_string();
+        assert!(!broken.verify("secret"));
+    }
+
+    #[test]
+    fn authenticate() {
+        assert_eq!(Store::default().authenticate(None), Ok(Scope::Admin));
+
+        let store = store();
+        assert_eq!(store.authenticate(Some("Bearer abc123")), Ok(Scope::Speak));
+        assert_eq!(
+            store.authenticate(Some("bearer  abc123 ")),
+            Ok(Scope::Speak)
+        );
+        assert_eq!(
+            store.authenticate(Some(&basic("admin", "change-me"))),
+            Ok(Scope::Admin)
+        );
+        assert_eq!(
+            store.authenticate(Some(&basic("admin", "change-me").replace("Basic", "BASIC"))),
+            Ok(Scope::Admin)
+        );
+
+        assert_eq!(store.authenticate(None), Err(Denied::Missing));
+        let invalid = [
+            "Bearer wrong".to_string(),
+            "Bearer".to_string(),
+            "abc123".to_string(),
+            "Digest abc123".to_string(),
+            "Basic !!!".to_string(),
+            basic("admin", "wrong-password"),
+            basic("tv", "abc123"),
+            basic("nobody", "change-me"),
+            "Basic YWRtaW4=".to_string(),
+        ];
+        for authorization in &invalid {
+            assert_eq!(
+                store.authenticate(Some(authorization)),
+                Err(Denied::Invalid),
+                "{}",
+                authorization
+            );
+        }
+        // 密码凭据不能当作 token 使用
+        assert_eq!(
+            store.authenticate(Some("Bearer change-me")),
+            Err(Denied::Invalid)
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        assert!(Scope::Admin.allows(Scope::Speak));
+        assert!(Scope::Admin.allows(Scope::Admin));
+        assert!(Scope::Speak.allows(Scope::Speak));
+        assert!(!Scope::Speak.allows(Scope::Admin));
+        assert_eq!(Scope::Speak.to_string(), "speak");
+
+        let store = store();
+        let token = Some("Bearer abc123");
+        assert_eq!(store.authorize(token, Scope::Speak), Ok(Scope::Speak));
+        assert_eq!(store.authorize(token, Scope::Admin), Err(Denied::Forbidden));
+        let admin = basic("admin", "change-me");
+        assert_eq!(
+            store.authorize(Some(&admin), Scope::Admin),
+            Ok(Scope::Admin)
+        );
+        assert_eq!(store.authorize(None, Scope::Speak), Err(Denied::Missing));
+        assert_eq!(
+            store.authorize(Some("Bearer x"), Scope::Speak),
+            Err(Denied::Invalid)
+        );
+    }
+
+    #[test]
+    fn manage() {
+        let mut store = Store::default();
+        assert!(!store.is_enabled());
+
+        // 第一个凭据必须是 admin
+        let speak = Credential::new("tv", Kind::Token, Scope::Speak, "t", b"s", 1);
+        assert!(store.add(speak.clone(), 4).is_err());
+        let admin = Credential::new("admin", Kind::Token, Scope::Admin, "a", b"s", 1);
+        store.add(admin, 4).unwrap();
+        store.add(speak, 4).unwrap();
+        assert!(store.is_enabled());
+
+        // 同名凭据被替换，旧 token 失效
+        let replaced = Credential::new("tv", Kind::Token, Scope::Speak, "t2", b"s", 1);
+        store.add(replaced, 4).unwrap();
+        assert_eq!(store.list().len(), 2);
+        assert_eq!(store.authenticate(Some("Bearer t")), Err(Denied::Invalid));
+        assert_eq!(store.authenticate(Some("Bearer t2")), Ok(Scope::Speak));
+
+        // 不能把唯一的 admin 降级
+        let demoted = Credential::new("admin", Kind::Token, Scope::Speak, "a", b"s", 1);
+        assert!(store.add(demoted, 4).is_err());
+
+        for name in ["a", "b"] {
+            let credential = Credential::new(name, Kind::Token, Scope::Speak, name, b"s", 1);
+            store.add(credential, 4).unwrap();
+        }
+        let extra = Credential::new("c", Kind::Token, Scope::Speak, "c", b"s", 1);
+        assert!(store.add(extra, 4).is_err());
+        let invalid = Credential::new("bad name", Kind::Token, Scope::Speak, "x", b"s", 1);
+        assert!(store.add(invalid, 8).is_err());
+
+        // 删除凭据后立即失效，只剩 admin 时不能单独删除
+        assert!(store.remove("tv").unwrap());
+        assert!(!store.remove("tv").unwrap());
+        assert_eq!(store.authenticate(Some("Bearer t2")), Err(Denied::Invalid));
+        assert!(store.remove("admin").is_err());
+        store.remove("a").unwrap();
+        store.remove("b").unwrap();
+        store.remove("admin").unwrap();
+        assert!(!store.is_enabled());
+        assert_eq!(store.authenticate(None), Ok(Scope::Admin));
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate_name("kitchen-1_a").is_ok());
+        assert!(validate_name(&"a".repeat(NAME_MAX_LEN)).is_ok());
+        for name in ["", "a b", "厨房", "a:b", &"a".repeat(NAME_MAX_LEN + 1)] {
+            assert!(validate_name(name).is_err(), "{}", name);
+        }
+        assert!(validate_password("12345678").is_ok());
+        assert!(validate_password("一二三四五六七八").is_ok());
+        assert!(validate_password("1234567").is_err());
+    }
+
+    #[test]
+    fn hex_encoding() {
+        assert_eq!(hex(&[0x00, 0x9f, 0xff]), "009fff");
+        assert_eq!(unhex("009fFF"), Some(vec![0x00, 0x9f, 0xff]));
+        assert_eq!(unhex(""), Some(vec![]));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+}
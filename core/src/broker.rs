@@ -0,0 +1,199 @@
+// MQTT 设置、主题与消息格式
+//
+// 主题均以 topic 为前缀，默认为 esp32s3-tts/<设备 ID>：
+//   <topic>/command      订阅，消息格式与 WebSocket 相同，例如 {"type": "speak", "text": "你好"}
+//                        不是 JSON 对象的消息按文本播报
+//   <topic>/state        设备状态 {"volume": 3, "speaking": false, "queue_len": 0}，retain
+//   <topic>/event        播报事件与命令的 ack / error，例如 {"type": "started", "id": 5}
+//   <topic>/availability online / offline，retain，断开时由 broker 发布 offline (LWT)
+//   <topic>/telemetry    内存、运行时间、信号强度，见 discovery.rs
+
+use serde::{Deserialize, Serialize};
+
+pub const TOPIC_MAX_LEN: usize = 128;
+pub const FIELD_MAX_LEN: usize = 64;
+
+// 默认的主题前缀和 client ID
+pub const DEFAULT_NAME: &str = "esp32s3-tts";
+
+pub const ONLINE: &str = "online";
+pub const OFFLINE: &str = "offline";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub enabled: bool,
+    // mqtt://host:1883 或 mqtts://host:8883
+    pub url: String,
+    // 为空时使用设备 ID
+    pub client_id: String,
+    pub username: String,
+    // 为 None 时保留原来的密码，空字符串表示不使用密码
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    // 为空时使用默认前缀
+    pub topic: String,
+    // 订阅与发布的 QoS：0, 1, 2
+    pub qos: u8,
+    pub keep_alive_secs: u16,
+    // 发布 Home Assistant MQTT discovery 配置
+    pub discovery: bool,
+    pub discovery_prefix: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: false,
+            url: String::new(),
+            client_id: String::new(),
+            username: String::new(),
+            password: None,
+            topic: String::new(),
+            qos: 1,
+            keep_alive_secs: 30,
+            discovery: true,
+            discovery_prefix: "homeassistant".to_string(),
+        }
+    }
+}
+
+// GET /api/v1/mqtt 返回的设置，不含密码
+#[derive(Debug, Clone, Serialize)]
+pub struct Info {
+    #[serde(flatten)]
+    pub settings: Settings,
+    pub has_password: bool,
+    pub connected: bool,
+}
+
+impl Info {
+    pub fn new(settings: &Settings, connected: bool) -> Self {
+        let has_password = settings.password.as_deref().is_some_and(|p| !p.is_empty());
+        Info {
+            settings: Settings {
+                password: None,
+                ..settings.clone()
+            },
+            has_password,
+            connected,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.enabled && self.url.is_empty() {
+            anyhow::bail!("url is required");
+        }
+        if !self.url.is_empty()
+            && !["mqtt://", "mqtts://", "ws://", "wss://"]
+                .iter()
+                .any(|scheme| self.url.starts_with(scheme))
+        {
+            anyhow::bail!("url must start with mqtt://, mqtts://, ws:// or wss://");
+        }
+        if [&self.url, &self.topic, &self.discovery_prefix]
+            .iter()
+            .any(|field| field.len() > TOPIC_MAX_LEN)
+        {
+            anyhow::bail!(
+                "url, topic and discovery_prefix must be at most {} bytes",
+                TOPIC_MAX_LEN
+            );
+        }
+        let fields = [
+            self.client_id.as_str(),
+            self.username.as_str(),
+            self.password.as_deref().unwrap_or_default(),
+        ];
+        if fields.iter().any(|field| field.len() > FIELD_MAX_LEN) {
+            anyhow::bail!(
+                "client_id, username and password must be at most {} bytes",
+                FIELD_MAX_LEN
+            );
+        }
+        if !valid_topic(&self.topic) {
+            anyhow::bail!("invalid topic: {}", self.topic);
+        }
+        if self.discovery_prefix.is_empty() || !valid_topic(&self.discovery_prefix) {
+            anyhow::bail!("invalid discovery_prefix: {}", self.discovery_prefix);
+        }
+        if self.qos > 2 {
+            anyhow::bail!("qos must be 0, 1 or 2");
+        }
+        if self.keep_alive_secs == 0 {
+            anyhow::bail!("keep_alive_secs must be greater than 0");
+        }
+        Ok(())
+    }
+
+    // 修改设置时未提供密码则沿用原来的密码
+    pub fn merge_password(&mut self, old: &Settings) {
+        if self.password.is_none() {
+            self.password = old.password.clone();
+        }
+    }
+
+    pub fn client_id(&self, device_id: &str) -> String {
+        if self.client_id.is_empty() {
+            format!("{}-{}", DEFAULT_NAME, device_id)
+        } else {
+            self.client_id.clone()
+        }
+    }
+
+    pub fn topics(&self, device_id: &str) -> Topics {
+        let prefix = if self.topic.is_empty() {
+            format!("{}/{}", DEFAULT_NAME, device_id)
+        } else {
+            self.topic.clone()
+        };
+        Topics {
+            command: format!("{}/command", prefix),
+            state: format!("{}/state", prefix),
+            event: format!("{}/event", prefix),
+            availability: format!("{}/availability", prefix),
+            prefix,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topics {
+    pub prefix: String,
+    pub command: String,
+    pub state: String,
+    pub event: String,
+    pub availability: String,
+}
+
+// 发布到 <topic>/state 的设备状态
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct State {
+    pub volume: u8,
+    pub speaking: bool,
+    pub queue_len: usize,
+}
+
+// 不含通配符和空白，首尾不是 /
+fn valid_topic(topic: &str) -> bool {
+    !topic
+        .chars()
+        .any(|c| matches!(c, '+' | '#' | '\0') || c.is_whitespace())
+        && !topic.starts_with('/')
+        && !topic.ends_with('/')
+}
+
+// 把命令消息转换为 WebSocket 格式，纯文本按 speak 处理
+pub fn command(payload: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(payload);
+    if text.trim_start().starts_with('{') {
+        return payload.to_vec();
+    }
+    serde_json::to_vec(&serde_json::json!({
+        "type": "speak",
+        "text": text.trim(),
+    }))
+    .unwrap_or_default()
+}
@@ -0,0 +1,424 @@
+// HTTPS 证书：生成自签名的 ECDSA P-256 证书，校验上传的 PEM 证书 / 私钥
+//
+// 设备时间通常未同步，证书有效期固定为 2024-01-01 至 9999-12-31 (RFC 5280 中表示无过期时间)
+
+use std::net::Ipv4Addr;
+
+use base64::Engine;
+use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey, EncodePublicKey, LineEnding};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+// 证书 / 私钥 PEM 的最大长度，足够保存 RSA 4096 的证书链
+pub const PEM_MAX_LEN: usize = 8192;
+
+const NOT_BEFORE: &[u8] = b"240101000000Z";
+const NOT_AFTER: &[u8] = b"99991231235959Z";
+
+// ecdsa-with-SHA256 1.2.840.10045.4.3.2
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+// commonName 2.5.4.3
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+// subjectAltName 2.5.29.17
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+// rsaEncryption 1.2.840.113549.1.1.1
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+
+const KEY_LABELS: &[&str] = &["PRIVATE KEY", "EC PRIVATE KEY", "RSA PRIVATE KEY"];
+
+// PEM 格式的证书与私钥
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    pub cert: String,
+    pub key: String,
+}
+
+impl Identity {
+    // 私钥必须与证书 (证书链中的第一个) 的公钥匹配，只支持 P-256 与 RSA 私钥
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.cert.len() > PEM_MAX_LEN || self.key.len() > PEM_MAX_LEN {
+            anyhow::bail!("certificate or key exceeds {} bytes", PEM_MAX_LEN);
+        }
+        let cert = decode_pem(&self.cert, &["CERTIFICATE"])
+            .ok_or_else(|| anyhow::anyhow!("cert must be a PEM CERTIFICATE"))?;
+        let public_key =
+            public_key_info(&cert).ok_or_else(|| anyhow::anyhow!("invalid certificate"))?;
+        if self.key.contains("ENCRYPTED PRIVATE KEY") {
+            anyhow::bail!("encrypted private keys are not supported");
+        }
+        decode_pem(&self.key, KEY_LABELS)
+            .ok_or_else(|| anyhow::anyhow!("key must be a PEM PRIVATE KEY"))?;
+        if !key_matches(public_key, &self.key)? {
+            anyhow::bail!("key does not match the certificate");
+        }
+        Ok(())
+    }
+
+    // 证书 DER 的 SHA-256，格式与浏览器显示的一致，例如 "AB:CD:..."
+    pub fn fingerprint(&self) -> Option<String> {
+        let der = decode_pem(&self.cert, &["CERTIFICATE"])?;
+        let digest = Sha256::digest(&der);
+        let hex: Vec<String> = digest.iter().map(|b| format!("{:02X}", b)).collect();
+        Some(hex.join(":"))
+    }
+}
+
+// seed 为私钥，serial 为证书序列号，均由调用方提供随机数
+// 证书的 CN 和 DNS 名称为 name，ip 不为空时加入 IP 地址
+pub fn generate(
+    seed: &[u8; 32],
+    serial: &[u8; 16],
+    name: &str,
+    ip: Option<Ipv4Addr>,
+) -> anyhow::Result<Identity> {
+    let key = SigningKey::from_bytes(seed.into())?;
+    let public_key = key.verifying_key().to_public_key_der()?;
+
+    // 正整数且没有多余的前导 0
+    let mut serial = *serial;
+    serial[0] = (serial[0] & 0x7f) | 0x40;
+
+    let algorithm = sequence(&[&tlv(0x06, OID_ECDSA_SHA256)]);
+    let subject = sequence(&[&tlv(
+        0x31,
+        &sequence(&[&tlv(0x06, OID_COMMON_NAME), &tlv(0x0c, name.as_bytes())]),
+    )]);
+    let validity = sequence(&[&tlv(0x17, NOT_BEFORE), &tlv(0x18, NOT_AFTER)]);
+
+    // dNSName [2] 与 iPAddress [7]
+    let mut alt_names = tlv(0x82, name.as_bytes());
+    if let Some(ip) = ip {
+        alt_names.extend(tlv(0x87, &ip.octets()));
+    }
+    let extensions = tlv(
+        0xa3,
+        &sequence(&[&sequence(&[
+            &tlv(0x06, OID_SUBJECT_ALT_NAME),
+            &tlv(0x04, &tlv(0x30, &alt_names)),
+        ])]),
+    );
+
+    let tbs = sequence(&[
+        // version v3
+        &tlv(0xa0, &tlv(0x02, &[0x02])),
+        &tlv(0x02, &serial),
+        &algorithm,
+        &subject,
+        &validity,
+        &subject,
+        public_key.as_bytes(),
+        &extensions,
+    ]);
+    let signature: Signature = key.sign(&tbs);
+    let mut signature_bits = vec![0];
+    signature_bits.extend_from_slice(signature.to_der().as_bytes());
+    let cert = sequence(&[&tbs, &algorithm, &tlv(0x03, &signature_bits)]);
+
+    Ok(Identity {
+        cert: encode_pem("CERTIFICATE", &cert),
+        key: key.to_pkcs8_pem(LineEnding::LF)?.to_string(),
+    })
+}
+
+// 证书中 SubjectPublicKeyInfo 的 DER (含 tag 与长度)
+fn public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let (0x30, cert, _) = read_tlv(cert)? else {
+        return None;
+    };
+    let (0x30, tbs, _) = read_tlv(cert)? else {
+        return None;
+    };
+    // 可选的 version [0]，之后依次为 serial, signature, issuer, validity, subject
+    let mut rest = tbs;
+    if rest.first() == Some(&0xa0) {
+        rest = read_tlv(rest)?.2;
+    }
+    for _ in 0..5 {
+        rest = read_tlv(rest)?.2;
+    }
+    let (0x30, _, after) = read_tlv(rest)? else {
+        return None;
+    };
+    Some(&rest[..rest.len() - after.len()])
+}
+
+fn key_matches(public_key: &[u8], key: &str) -> anyhow::Result<bool> {
+    let invalid = || anyhow::anyhow!("invalid private key");
+    let unsupported = || anyhow::anyhow!("only P-256 and RSA private keys are supported");
+
+    if let Some(der) = decode_pem(key, &["EC PRIVATE KEY"]) {
+        let key = p256::SecretKey::from_sec1_der(&der).map_err(|_| unsupported())?;
+        return Ok(key.public_key().to_public_key_der()?.as_bytes() == public_key);
+    }
+    if let Some(der) = decode_pem(key, &["RSA PRIVATE KEY"]) {
+        return Ok(rsa_matches(public_key, &der));
+    }
+
+    // PrivateKeyInfo：version, algorithm, privateKey
+    let der = decode_pem(key, &["PRIVATE KEY"]).ok_or_else(invalid)?;
+    let (_, info, _) = read_tlv(&der).ok_or_else(invalid)?;
+    let (_, _, rest) = read_tlv(info).ok_or_else(invalid)?;
+    let (_, algorithm, rest) = read_tlv(rest).ok_or_else(invalid)?;
+    let (_, oid, _) = read_tlv(algorithm).ok_or_else(invalid)?;
+    if oid == OID_RSA_ENCRYPTION {
+        let (_, private_key, _) = read_tlv(rest).ok_or_else(invalid)?;
+        return Ok(rsa_matches(public_key, private_key));
+    }
+    let key = p256::SecretKey::from_pkcs8_der(&der).map_err(|_| unsupported())?;
+    Ok(key.public_key().to_public_key_der()?.as_bytes() == public_key)
+}
+
+// RSAPrivateKey 中的 modulus 与 publicExponent 和证书中的 RSAPublicKey 相同
+fn rsa_matches(public_key: &[u8], private_key: &[u8]) -> bool {
+    let public = (|| {
+        let (_, info, _) = read_tlv(public_key)?;
+        let (_, _, rest) = read_tlv(info)?;
+        let (0x03, bits, _) = read_tlv(rest)? else {
+            return None;
+        };
+        let (_, key, _) = read_tlv(bits.get(1..)?)?;
+        let (_, modulus, rest) = read_tlv(key)?;
+        let (_, exponent, _) = read_tlv(rest)?;
+        Some((modulus, exponent))
+    })();
+    let private = (|| {
+        let (_, key, _) = read_tlv(private_key)?;
+        let (_, _, rest) = read_tlv(key)?;
+        let (_, modulus, rest) = read_tlv(rest)?;
+        let (_, exponent, _) = read_tlv(rest)?;
+        Some((modulus, exponent))
+    })();
+    public.is_some() && public == private
+}
+
+// 读取一个 DER TLV，返回 (tag, 内容, 之后的数据)
+fn read_tlv(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = der.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+    let (len, rest) = if first < 0x80 {
+        (first as usize, rest)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let len = rest[..count]
+            .iter()
+            .fold(0usize, |len, b| len << 8 | *b as usize);
+        (len, &rest[count..])
+    };
+    if rest.len() < len {
+        return None;
+    }
+    Some((tag, &rest[..len], &rest[len..]))
+}
+
+fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+fn sequence(parts: &[&[u8]]) -> Vec<u8> {
+    tlv(0x30, &parts.concat())
+}
+
+fn encode_pem(label: &str, der: &[u8]) -> String {
+    let body = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in body.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+// 返回第一个标签匹配的 PEM 块的内容
+fn decode_pem(pem: &str, labels: &[&str]) -> Option<Vec<u8>> {
+    labels.iter().find_map(|label| {
+        let begin = format!("-----BEGIN {}-----", label);
+        let end = format!("-----END {}-----", label);
+        let start = pem.find(&begin)? + begin.len();
+        let len = pem[start..].find(&end)?;
+        let body: String = pem[start..start + len]
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        base64::engine::general_purpose::STANDARD
+            .decode(body)
+            .ok()
+            .filter(|der| !der.is_empty())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use p256::ecdsa::{signature::Verifier, VerifyingKey};
+
+    use super::*;
+
+    fn identity(seed: u8) -> Identity {
+        generate(
+            &[seed; 32],
+            &[0xff; 16],
+            "esp32s3-tts",
+            Some(Ipv4Addr::new(192, 168, 71, 1)),
+        )
+        .unwrap()
+    }
+
+    // 依次读取 SEQUENCE 中的各个元素
+    fn elements(mut der: &[u8]) -> Vec<(u8, &[u8])> {
+        let mut out = Vec::new();
+        while !der.is_empty() {
+            let (tag, content, rest) = read_tlv(der).unwrap();
+            out.push((tag, content));
+            der = rest;
+        }
+        out
+    }
+
+    #[test]
+    fn generated_certificate_parses() {
+        let identity = identity(7);
+        identity.validate().unwrap();
+        let der = decode_pem(&identity.cert, &["CERTIFICATE"]).unwrap();
+
+        let (tag, cert, rest) = read_tlv(&der).unwrap();
+        assert_eq!((tag, rest.len()), (0x30, 0));
+        let cert = elements(cert);
+        assert_eq!(cert.len(), 3);
+        let (tbs_tag, tbs) = cert[0];
+        assert_eq!(tbs_tag, 0x30);
+        assert_eq!(cert[1], (0x30, &tlv(0x06, OID_ECDSA_SHA256)[..]));
+
+        let fields = elements(tbs);
+        assert_eq!(fields.len(), 8);
+        // v3，序列号为正数且没有前导 0
+        assert_eq!(fields[0], (0xa0, &[0x02, 0x01, 0x02][..]));
+        let (serial_tag, serial) = fields[1];
+        assert_eq!((serial_tag, serial.len()), (0x02, 16));
+        assert_eq!(serial[0] & 0xc0, 0x40);
+        assert_eq!(fields[2], cert[1]);
+
+        let name = tlv(0x0c, b"esp32s3-tts");
+        assert!(fields[3].1.ends_with(&name));
+        assert_eq!(fields[3], fields[5]);
+        let validity = elements(fields[4].1);
+        assert_eq!(validity, [(0x17, NOT_BEFORE), (0x18, NOT_AFTER)]);
+
+        let key = SigningKey::from_bytes(&[7; 32].into()).unwrap();
+        let public_key = key.verifying_key().to_public_key_der().unwrap();
+        assert_eq!(public_key_info(&der), Some(public_key.as_bytes()));
+
+        // subjectAltName：dNSName 与 iPAddress
+        let extensions = fields[7].1;
+        assert!(extensions
+            .windows(OID_SUBJECT_ALT_NAME.len())
+            .any(|w| w == OID_SUBJECT_ALT_NAME));
+        assert!(extensions.ends_with(&[0x87, 4, 192, 168, 71, 1]));
+
+        // 签名覆盖完整的 tbsCertificate
+        let (bits_tag, bits) = cert[2];
+        assert_eq!((bits_tag, bits[0]), (0x03, 0));
+        let signature = Signature::from_der(&bits[1..]).unwrap();
+        VerifyingKey::from(&key)
+            .verify(&tlv(0x30, tbs), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn key_must_match() {
+        let a = identity(1);
+        let b = identity(2);
+        let mismatched = Identity {
+            cert: a.cert.clone(),
+            key: b.key.clone(),
+        };
+        let e = mismatched.validate().unwrap_err();
+        assert_eq!(e.to_string(), "key does not match the certificate");
+
+        // SEC1 格式的 EC 私钥
+        let key = p256::SecretKey::from_bytes(&[1; 32].into()).unwrap();
+        let sec1 = Identity {
+            cert: a.cert.clone(),
+            key: key.to_sec1_pem(LineEnding::LF).unwrap().to_string(),
+        };
+        sec1.validate().unwrap();
+    }
+
+    #[test]
+    fn rsa_key_match() {
+        let modulus = tlv(0x02, &[0x00, 0xc1, 0x23, 0x45, 0x67]);
+        let exponent = tlv(0x02, &[0x01, 0x00, 0x01]);
+        let algorithm = sequence(&[&tlv(0x06, OID_RSA_ENCRYPTION), &tlv(0x05, &[])]);
+        let mut bits = vec![0];
+        bits.extend(sequence(&[&modulus, &exponent]));
+        let public_key = sequence(&[&algorithm, &tlv(0x03, &bits)]);
+
+        let version = tlv(0x02, &[0]);
+        let private_key =
+            |modulus: &[u8]| sequence(&[&version, modulus, &exponent, &tlv(0x02, &[0x33])]);
+        assert!(rsa_matches(&public_key, &private_key(&modulus)));
+        let other = tlv(0x02, &[0x00, 0xc1, 0x23, 0x45, 0x68]);
+        assert!(!rsa_matches(&public_key, &private_key(&other)));
+        assert!(!rsa_matches(&public_key, &[0x30, 0x00]));
+
+        // PKCS#8 包装的 RSA 私钥
+        let pkcs8 = sequence(&[&version, &algorithm, &tlv(0x04, &private_key(&modulus))]);
+        let pem = encode_pem("PRIVATE KEY", &pkcs8);
+        assert!(key_matches(&public_key, &pem).unwrap());
+    }
+
+    #[test]
+    fn invalid_identities() {
+        let valid = identity(3);
+        let cases = [
+            ("not pem".to_string(), valid.key.clone()),
+            (
+                encode_pem("CERTIFICATE", &[0x30, 0x03, 0x02, 0x01]),
+                valid.key.clone(),
+            ),
+            (valid.cert.clone(), "not pem".to_string()),
+            (
+                valid.cert.clone(),
+                encode_pem("ENCRYPTED PRIVATE KEY", &[0x30, 0x00]),
+            ),
+            (valid.cert.clone(), "x".repeat(PEM_MAX_LEN + 1)),
+        ];
+        for (cert, key) in cases {
+            assert!(Identity { cert, key }.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn read_tlv_lengths() {
+        let long = tlv(0x04, &[0xaa; 300]);
+        assert_eq!(&long[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        let (tag, content, rest) = read_tlv(&long).unwrap();
+        assert_eq!((tag, content.len(), rest.len()), (0x04, 300, 0));
+        assert!(read_tlv(&long[..long.len() - 1]).is_none());
+        assert!(read_tlv(&[0x30, 0x80]).is_none());
+        assert!(read_tlv(&[0x30]).is_none());
+    }
+
+    #[test]
+    fn fingerprint_format() {
+        let fingerprint = identity(4).fingerprint().unwrap();
+        assert_eq!(fingerprint.len(), 32 * 3 - 1);
+        assert!(fingerprint
+            .split(':')
+            .all(|b| b.len() == 2 && b.chars().all(|c| c.is_ascii_hexdigit())));
+    }
+}
@@ -0,0 +1,268 @@
+// 音频流式解码：WAV (PCM / IMA ADPCM / MS ADPCM) 与 MP3
+// 每次只解码一个 block / frame 并重采样到输出采样率，内存占用与文件大小无关
+
+use std::io::{Read, Seek, SeekFrom};
+
+use anyhow::{anyhow, bail};
+use symphonia_bundle_mp3::{MpaDecoder, MpaReader};
+use symphonia_core::audio::SampleBuffer;
+use symphonia_core::codecs::{Decoder, DecoderOptions};
+use symphonia_core::errors::Error as SymphoniaError;
+use symphonia_core::formats::{FormatOptions, FormatReader};
+use symphonia_core::io::{MediaSourceStream, ReadOnlySource};
+
+use crate::adpcm;
+use crate::wav::{self, Codec};
+
+// 用于识别格式的文件头长度
+const PROBE_LEN: usize = 4096;
+// PCM 每次读取的帧数
+const PCM_CHUNK_FRAMES: usize = 1024;
+
+pub trait Reader: Read + Seek + Send + Sync + 'static {}
+
+impl<T: Read + Seek + Send + Sync + 'static> Reader for T {}
+
+// 单声道、源采样率的解码器
+trait Source: Send {
+    fn sample_rate(&self) -> u32;
+    fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<i16>>>;
+}
+
+// ID3 标签，或完整有效的 MPEG 音频帧头，避免把裸 PCM 误认为 MP3
+pub fn is_mp3(buf: &[u8]) -> bool {
+    if buf.starts_with(b"ID3") {
+        return true;
+    }
+    let [b0, b1, b2, ..] = *buf else {
+        return false;
+    };
+    let sync = b0 == 0xFF && b1 & 0xE0 == 0xE0;
+    // 版本 01 与 layer 00 为保留值
+    let version = (b1 >> 3) & 0x03;
+    let layer = (b1 >> 1) & 0x03;
+    // 码率索引 1111 与采样率索引 11 无效
+    let bitrate = b2 >> 4;
+    let sample_rate = (b2 >> 2) & 0x03;
+    sync && version != 1 && layer != 0 && bitrate != 0x0F && sample_rate != 3
+}
+
+// 根据文件头判断格式，返回文件扩展名
+pub fn probe(buf: &[u8]) -> anyhow::Result<&'static str> {
+    if wav::is_wav(buf) {
+        wav::parse_header(buf)?;
+        Ok("wav")
+    } else if is_mp3(buf) {
+        Ok("mp3")
+    } else {
+        bail!("unsupported audio format")
+    }
+}
+
+pub struct Stream {
+    source: Box<dyn Source>,
+    resampler: wav::Resampler,
+}
+
+impl Stream {
+    // 打开音频流，输出单声道 sample_rate 采样率的 PCM
+    pub fn open(mut reader: impl Reader, sample_rate: u32) -> anyhow::Result<Self> {
+        let mut head = Vec::with_capacity(PROBE_LEN);
+        (&mut reader)
+            .take(PROBE_LEN as u64)
+            .read_to_end(&mut head)?;
+
+        let source: Box<dyn Source> = match probe(&head)? {
+            "wav" => {
+                let header = wav::parse_header(&head)?;
+                reader.seek(SeekFrom::Start(header.data_offset as u64))?;
+                Box::new(WavSource::new(reader, header))
+            }
+            _ => {
+                reader.seek(SeekFrom::Start(0))?;
+                Box::new(Mp3Source::new(reader)?)
+            }
+        };
+
+        Ok(Stream {
+            resampler: wav::Resampler::new(source.sample_rate(), sample_rate),
+            source,
+        })
+    }
+
+    pub fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<i16>>> {
+        match self.source.next_chunk()? {
+            Some(pcm) => Ok(Some(self.resampler.process(&pcm))),
+            None => Ok(None),
+        }
+    }
+
+    // 已生成的单声道 PCM，例如 tone 模块生成的音调
+    pub fn from_pcm(pcm: Vec<i16>, sample_rate: u32) -> Self {
+        Stream {
+            source: Box::new(PcmSource {
+                pcm,
+                pos: 0,
+                sample_rate,
+            }),
+            resampler: wav::Resampler::new(sample_rate, sample_rate),
+        }
+    }
+
+    // 解码全部数据
+    pub fn decode_all(mut self) -> anyhow::Result<Vec<i16>> {
+        let mut out = Vec::new();
+        while let Some(pcm) = self.next_chunk()? {
+            out.extend_from_slice(&pcm);
+        }
+        Ok(out)
+    }
+}
+
+struct PcmSource {
+    pcm: Vec<i16>,
+    pos: usize,
+    sample_rate: u32,
+}
+
+impl Source for PcmSource {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<i16>>> {
+        if self.pos >= self.pcm.len() {
+            return Ok(None);
+        }
+        let end = (self.pos + PCM_CHUNK_FRAMES).min(self.pcm.len());
+        let chunk = self.pcm[self.pos..end].to_vec();
+        self.pos = end;
+        Ok(Some(chunk))
+    }
+}
+
+struct WavSource<R> {
+    reader: std::io::Take<R>,
+    header: wav::Header,
+    buf: Vec<u8>,
+}
+
+impl<R: Read> WavSource<R> {
+    fn new(reader: R, header: wav::Header) -> Self {
+        let block = match header.codec {
+            Codec::Pcm => header.format.block_align() * PCM_CHUNK_FRAMES,
+            Codec::ImaAdpcm { block_align } | Codec::MsAdpcm { block_align, .. } => block_align,
+        };
+        WavSource {
+            reader: reader.take(header.data_len as u64),
+            header,
+            buf: vec![0; block],
+        }
+    }
+
+    // 读满一个 block，文件结束时返回实际读取的长度
+    fn fill(&mut self) -> anyhow::Result<usize> {
+        let mut n = 0;
+        while n < self.buf.len() {
+            let read = self.reader.read(&mut self.buf[n..])?;
+            if read == 0 {
+                break;
+            }
+            n += read;
+        }
+        Ok(n)
+    }
+}
+
+impl<R: Read + Send> Source for WavSource<R> {
+    fn sample_rate(&self) -> u32 {
+        self.header.format.sample_rate
+    }
+
+    fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<i16>>> {
+        let n = self.fill()?;
+        let channels = self.header.format.channels as usize;
+        let block = &self.buf[..n];
+
+        let pcm = match &self.header.codec {
+            Codec::Pcm => wav::decode(&self.header.format, block),
+            Codec::ImaAdpcm { .. } => {
+                let mut out = Vec::new();
+                adpcm::decode_ima_block(block, channels, &mut out);
+                wav::downmix(&out, channels)
+            }
+            Codec::MsAdpcm { coefs, .. } => {
+                let mut out = Vec::new();
+                adpcm::decode_ms_block(block, channels, coefs, &mut out);
+                wav::downmix(&out, channels)
+            }
+        };
+
+        if pcm.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(pcm))
+        }
+    }
+}
+
+struct Mp3Source {
+    format: MpaReader,
+    decoder: MpaDecoder,
+    sample_rate: u32,
+}
+
+impl Mp3Source {
+    fn new(reader: impl Reader) -> anyhow::Result<Self> {
+        let mss = MediaSourceStream::new(Box::new(ReadOnlySource::new(reader)), Default::default());
+        let format = MpaReader::try_new(mss, &FormatOptions::default())?;
+        let track = format
+            .default_track()
+            .ok_or_else(|| anyhow!("mp3 track not found"))?;
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .ok_or_else(|| anyhow!("mp3 sample rate unknown"))?;
+        let decoder = MpaDecoder::try_new(&track.codec_params, &DecoderOptions::default())?;
+
+        Ok(Mp3Source {
+            format,
+            decoder,
+            sample_rate,
+        })
+    }
+}
+
+impl Source for Mp3Source {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn next_chunk(&mut self) -> anyhow::Result<Option<Vec<i16>>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, spec);
+                    samples.copy_interleaved_ref(decoded);
+                    return Ok(Some(wav::downmix(samples.samples(), spec.channels.count())));
+                }
+                // 损坏的帧直接跳过
+                Err(SymphoniaError::DecodeError(e)) => {
+                    log::warn!("mp3 decode error: {}", e);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,181 @@
+// Home Assistant MQTT discovery：生成各实体的配置主题和内容
+//
+// 配置发布到 <prefix>/<component>/<node_id>/<object_id>/config (retain)，node_id 由 MAC 生成
+// 实体的命令和状态使用 broker.rs 中的主题：
+//   notify / text  播报，消息为纯文本，发送到 <topic>/command
+//   number         音量 0 ~ 100，状态来自 <topic>/state
+//   button         停止播报
+//   sensor         空闲内存、运行时间、WiFi 信号强度，来自 <topic>/telemetry
+
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::broker::{self, Topics};
+
+// 每隔 MQTT_TELEMETRY_MS 发布到 <topic>/telemetry
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Telemetry {
+    pub heap_free: usize,
+    pub psram_free: usize,
+    pub uptime_secs: u64,
+    // AP 模式下为已连接客户端中最强的信号，没有客户端时为 null
+    pub rssi: Option<i8>,
+}
+
+// 设备信息，所有实体共用
+#[derive(Debug, Clone)]
+pub struct Device<'a> {
+    // 十二位十六进制的 MAC，例如 "a1b2c3d4e5f6"
+    pub id: &'a str,
+    pub name: &'a str,
+    pub model: &'a str,
+    pub version: &'a str,
+}
+
+impl Device<'_> {
+    pub fn node_id(&self) -> String {
+        format!("{}_{}", broker::DEFAULT_NAME.replace('-', "_"), self.id)
+    }
+
+    fn mac(&self) -> String {
+        let bytes: Vec<&str> = (0..self.id.len())
+            .step_by(2)
+            .filter_map(|i| self.id.get(i..i + 2))
+            .collect();
+        bytes.join(":")
+    }
+
+    fn info(&self) -> Value {
+        json!({
+            "identifiers": [self.node_id()],
+            "connections": [["mac", self.mac()]],
+            "name": self.name,
+            "model": self.model,
+            "sw_version": self.version,
+        })
+    }
+}
+
+// Home Assistant 上线时发布 online，收到后重新发布配置
+pub fn status_topic(prefix: &str) -> String {
+    format!("{}/status", prefix)
+}
+
+pub fn telemetry_topic(topics: &Topics) -> String {
+    format!("{}/telemetry", topics.prefix)
+}
+
+// 返回 (主题, 配置 JSON)
+pub fn configs(prefix: &str, device: &Device, topics: &Topics) -> Vec<(String, String)> {
+    let telemetry = telemetry_topic(topics);
+    let entities = [
+        (
+            "notify",
+            "speak",
+            json!({
+                "name": "Speak",
+                "icon": "mdi:account-voice",
+                "command_topic": topics.command,
+            }),
+        ),
+        (
+            "text",
+            "text",
+            json!({
+                "name": "Text",
+                "icon": "mdi:account-voice",
+                "command_topic": topics.command,
+            }),
+        ),
+        (
+            "number",
+            "volume",
+            json!({
+                "name": "Volume",
+                "icon": "mdi:volume-high",
+                "command_topic": topics.command,
+                "command_template": "{\"type\": \"volume\", \"level\": {{ value | int }}}",
+                "state_topic": topics.state,
+                "value_template": "{{ value_json.volume }}",
+                "min": 0,
+                "max": 100,
+                "step": 1,
+                "mode": "slider",
+            }),
+        ),
+        (
+            "button",
+            "stop",
+            json!({
+                "name": "Stop",
+                "icon": "mdi:stop",
+                "command_topic": topics.command,
+                "payload_press": "{\"type\": \"stop\"}",
+            }),
+        ),
+        (
+            "sensor",
+            "heap_free",
+            json!({
+                "name": "Free heap",
+                "state_topic": telemetry,
+                "value_template": "{{ value_json.heap_free }}",
+                "unit_of_measurement": "B",
+                "device_class": "data_size",
+                "state_class": "measurement",
+                "entity_category": "diagnostic",
+            }),
+        ),
+        (
+            "sensor",
+            "uptime",
+            json!({
+                "name": "Uptime",
+                "state_topic": telemetry,
+                "value_template": "{{ value_json.uptime_secs }}",
+                "unit_of_measurement": "s",
+                "device_class": "duration",
+                "state_class": "total_increasing",
+                "entity_category": "diagnostic",
+            }),
+        ),
+        (
+            "sensor",
+            "rssi",
+            json!({
+                "name": "WiFi signal",
+                "state_topic": telemetry,
+                "value_template": "{{ value_json.rssi }}",
+                "unit_of_measurement": "dBm",
+                "device_class": "signal_strength",
+                "state_class": "measurement",
+                "entity_category": "diagnostic",
+            }),
+        ),
+    ];
+
+    let node_id = device.node_id();
+    entities
+        .into_iter()
+        .map(|(component, object_id, entity)| {
+            let mut config = match entity {
+                Value::Object(config) => config,
+                _ => Map::new(),
+            };
+            config.insert(
+                "unique_id".to_string(),
+                json!(format!("{}_{}", node_id, object_id)),
+            );
+            config.insert(
+                "object_id".to_string(),
+                json!(format!("{}_{}", node_id, object_id)),
+            );
+            config.insert("availability_topic".to_string(), json!(topics.availability));
+            config.insert("payload_available".to_string(), json!(broker::ONLINE));
+            config.insert("payload_not_available".to_string(), json!(broker::OFFLINE));
+            config.insert("device".to_string(), device.info());
+            let topic = format!("{}/{}/{}/{}/config", prefix, component, node_id, object_id);
+            (topic, Value::Object(config).to_string())
+        })
+        .collect()
+}
@@ -0,0 +1,329 @@
+// 语音效果：WSOLA 变速不变调与变调不变速
+// 作用于 TTS 引擎输出的 PCM，流式处理
+
+use serde::{Deserialize, Serialize};
+
+use crate::wav;
+
+// WSOLA 帧长与相似度搜索范围 (ms)
+const FRAME_MS: u32 = 20;
+const SEARCH_MS: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    // 语速倍数，> 1 加快
+    pub tempo: f32,
+    // 音调倍数，> 1 升高
+    pub pitch: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tempo: 1.0,
+            pitch: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(0.5..=2.0).contains(&self.tempo) || !(0.5..=2.0).contains(&self.pitch) {
+            anyhow::bail!("invalid voice effect: {:?}", self);
+        }
+        Ok(())
+    }
+
+    // 内置音色
+    pub fn preset(name: &str) -> Option<Self> {
+        let (tempo, pitch) = match name {
+            "normal" => (1.0, 1.0),
+            "child" => (1.1, 1.35),
+            "deep" => (0.95, 0.8),
+            _ => return None,
+        };
+        Some(Settings { tempo, pitch })
+    }
+}
+
+// 先用 WSOLA 把时长拉伸 pitch / tempo 倍，再重采样缩短 pitch 倍：
+// 最终时长为原来的 1 / tempo，音调为原来的 pitch 倍
+pub struct Effect {
+    wsola: Option<Wsola>,
+    resampler: Option<wav::Resampler>,
+}
+
+impl Effect {
+    pub fn new(settings: Settings, sample_rate: u32) -> Self {
+        let stretch = settings.pitch / settings.tempo;
+        let wsola = ((stretch - 1.0).abs() > 0.01).then(|| Wsola::new(stretch, sample_rate));
+        let resampler = ((settings.pitch - 1.0).abs() > 0.01).then(|| {
+            wav::Resampler::new((sample_rate as f32 * settings.pitch) as u32, sample_rate)
+        });
+        Effect { wsola, resampler }
+    }
+
+    pub fn process(&mut self, pcm: &[i16]) -> Vec<i16> {
+        let stretched = match self.wsola.as_mut() {
+            Some(wsola) => wsola.process(pcm),
+            None => pcm.to_vec(),
+        };
+        self.resample(stretched)
+    }
+
+    // 输出缓存中剩余的数据
+    pub fn flush(&mut self) -> Vec<i16> {
+        let stretched = match self.wsola.as_mut() {
+            Some(wsola) => wsola.flush(),
+            None => Vec::new(),
+        };
+        self.resample(stretched)
+    }
+
+    fn resample(&mut self, pcm: Vec<i16>) -> Vec<i16> {
+        match self.resampler.as_mut() {
+            Some(resampler) => resampler.process(&pcm),
+            None => pcm,
+        }
+    }
+}
+
+// 波形相似叠加 (WSOLA)：输出按固定步长叠加 Hann 窗帧，
+// 输入帧位置在名义位置附近搜索与上一帧自然延续最相似的位置，保持基音周期连续
+pub struct Wsola {
+    frame: usize,
+    hop: usize,
+    search: usize,
+    // 输入步长 = hop / stretch
+    input_hop: f64,
+    window: Vec<f32>,
+    // 尚未丢弃的输入，input[0] 对应绝对位置 base
+    input: Vec<f32>,
+    base: usize,
+    // 下一帧的名义输入位置
+    nominal: f64,
+    // 上一帧的自然延续位置，第一帧为 None
+    natural: Option<usize>,
+    // 重叠相加缓存，长度为 frame
+    ola: Vec<f32>,
+}
+
+impl Wsola {
+    // stretch: 输出时长 / 输入时长
+    pub fn new(stretch: f32, sample_rate: u32) -> Self {
+        let frame = (sample_rate * FRAME_MS / 1000) as usize;
+        let hop = frame / 2;
+        let window = (0..frame)
+            .map(|i| {
+                let x = std::f32::consts::PI * 2.0 * i as f32 / frame as f32;
+                0.5 - 0.5 * x.cos()
+            })
+            .collect();
+        Wsola {
+            frame,
+            hop,
+            search: (sample_rate * SEARCH_MS / 1000) as usize,
+            input_hop: hop as f64 / stretch as f64,
+            window,
+            input: Vec::new(),
+            base: 0,
+            nominal: 0.0,
+            natural: None,
+            ola: vec![0.0; frame],
+        }
+    }
+
+    pub fn process(&mut self, pcm: &[i16]) -> Vec<i16> {
+        self.input.extend(pcm.iter().map(|s| *s as f32));
+
+        let mut out = Vec::new();
+        while let Some(pos) = self.next_position() {
+            let start = pos - self.base;
+            for (i, acc) in self.ola.iter_mut().enumerate() {
+                *acc += self.input[start + i] * self.window[i];
+            }
+            out.extend(self.ola[..self.hop].iter().map(|s| to_i16(*s)));
+            self.ola.copy_within(self.hop.., 0);
+            let len = self.ola.len();
+            self.ola[len - self.hop..].fill(0.0);
+
+            self.natural = Some(pos + self.hop);
+            self.nominal += self.input_hop;
+            self.discard();
+        }
+        out
+    }
+
+    // 剩余输入不足一帧，直接输出重叠缓存
+    pub fn flush(&mut self) -> Vec<i16> {
+        let out = self.ola[..self.frame - self.hop]
+            .iter()
+            .map(|s| to_i16(*s))
+            .collect();
+        self.ola.fill(0.0);
+        self.input.clear();
+        out
+    }
+
+    // 下一帧的输入位置，输入数据不足时返回 None
+    fn next_position(&self) -> Option<usize> {
+        let end = self.base + self.input.len();
+        let nominal = self.nominal as usize;
+        let Some(natural) = self.natural else {
+            return (nominal + self.frame <= end).then_some(nominal);
+        };
+        if natural + self.hop > end || nominal + self.search + self.frame > end {
+            return None;
+        }
+
+        // 在名义位置附近找与自然延续的前半帧相关性最大的位置
+        let target = &self.input[natural - self.base..natural - self.base + self.hop];
+        let from = nominal.saturating_sub(self.search).max(self.base);
+        (from..=nominal + self.search)
+            .map(|pos| {
+                let candidate = &self.input[pos - self.base..pos - self.base + self.hop];
+                let score: f32 = candidate.iter().zip(target).map(|(a, b)| a * b).sum();
+                (pos, score)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(pos, _)| pos)
+    }
+
+    // 丢弃之后不会再用到的输入
+    fn discard(&mut self) {
+        let keep_from = (self.nominal as usize)
+            .saturating_sub(self.search)
+            .min(self.natural.unwrap_or(0));
+        if keep_from > self.base {
+            let n = (keep_from - self.base).min(self.input.len());
+            self.input.drain(..n);
+            self.base += n;
+        }
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    sample.clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 16000;
+
+    fn sine(freq: f32, samples: usize) -> Vec<i16> {
+        (0..samples)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                (10000.0 * (2.0 * std::f32::consts::PI * freq * t).sin()) as i16
+            })
+            .collect()
+    }
+
+    // 分块处理，模拟 TTS 引擎的输出
+    fn run(settings: Settings, input: &[i16]) -> Vec<i16> {
+        let mut effect = Effect::new(settings, SAMPLE_RATE);
+        let mut out: Vec<i16> = input
+            .chunks(700)
+            .flat_map(|chunk| effect.process(chunk))
+            .collect();
+        out.extend(effect.flush());
+        out
+    }
+
+    // 按 1Hz 步长扫描能量最大的频率
+    fn dominant_freq(pcm: &[i16], from: u32, to: u32) -> u32 {
+        let power = |freq: u32| {
+            let w = 2.0 * std::f64::consts::PI * freq as f64 / SAMPLE_RATE as f64;
+            let (re, im) = pcm
+                .iter()
+                .enumerate()
+                .fold((0.0, 0.0), |(re, im), (i, &s)| {
+                    let phase = w * i as f64;
+                    (re + s as f64 * phase.cos(), im - s as f64 * phase.sin())
+                });
+            re * re + im * im
+        };
+        (from..=to)
+            .max_by(|a, b| power(*a).total_cmp(&power(*b)))
+            .unwrap()
+    }
+
+    #[test]
+    fn tempo_changes_length() {
+        let input = sine(220.0, SAMPLE_RATE as usize * 2);
+        for tempo in [0.5, 0.8, 1.25, 2.0] {
+            let out = run(Settings { tempo, pitch: 1.0 }, &input);
+            let expected = input.len() as f32 / tempo;
+            let error = (out.len() as f32 - expected).abs() / expected;
+            assert!(
+                error < 0.03,
+                "tempo {}: {} samples, expected {}",
+                tempo,
+                out.len(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn tempo_keeps_pitch() {
+        let input = sine(300.0, SAMPLE_RATE as usize);
+        let out = run(
+            Settings {
+                tempo: 1.5,
+                pitch: 1.0,
+            },
+            &input,
+        );
+        let middle = &out[out.len() / 4..out.len() * 3 / 4];
+        let freq = dominant_freq(middle, 200, 450);
+        assert!((295..=305).contains(&freq), "dominant frequency {}", freq);
+    }
+
+    #[test]
+    fn pitch_scales_frequency() {
+        let input = sine(300.0, SAMPLE_RATE as usize);
+        for pitch in [0.8, 1.35, 2.0] {
+            let out = run(Settings { tempo: 1.0, pitch }, &input);
+            let error = (out.len() as f32 - input.len() as f32).abs() / input.len() as f32;
+            assert!(error < 0.03, "pitch {}: {} samples", pitch, out.len());
+
+            let middle = &out[out.len() / 4..out.len() * 3 / 4];
+            let expected = 300.0 * pitch;
+            let freq = dominant_freq(middle, 150, 650) as f32;
+            assert!(
+                (freq - expected).abs() / expected < 0.02,
+                "pitch {}: dominant frequency {}, expected {}",
+                pitch,
+                freq,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn unity_is_passthrough() {
+        let input = sine(440.0, 1000);
+        assert_eq!(run(Settings::default(), &input), input);
+    }
+
+    #[test]
+    fn validate_range() {
+        assert!(Settings::default().validate().is_ok());
+        assert!(Settings::preset("child").unwrap().validate().is_ok());
+        assert!(Settings {
+            tempo: 2.5,
+            pitch: 1.0
+        }
+        .validate()
+        .is_err());
+        assert!(Settings {
+            tempo: 1.0,
+            pitch: 0.4
+        }
+        .validate()
+        .is_err());
+    }
+}
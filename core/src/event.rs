@@ -0,0 +1,44 @@
+use std::sync::{mpsc, Mutex};
+
+use serde::Serialize;
+
+// 播报生命周期及语音交互事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    // 已进入队列
+    Queued { id: u32 },
+    // 开始播放
+    Started { id: u32 },
+    // 播放完成
+    Finished { id: u32 },
+    // 被停止
+    Stopped { id: u32 },
+    // 检测到唤醒词，word 为模型中的唤醒词序号
+    Wake { word: i32 },
+    // 识别到命令词
+    Command { phrase: String },
+    // 音量变化
+    Volume { level: u8 },
+    // 按键按下：k0, up, down
+    Button { button: &'static str },
+    // WiFi 状态变化：ap_started, ap_stopped, client_connected, client_disconnected
+    Wifi { change: &'static str, clients: usize },
+}
+
+static SUBSCRIBERS: Mutex<Vec<mpsc::Sender<Event>>> = Mutex::new(Vec::new());
+
+// 订阅事件，返回的 Receiver 被 drop 后自动取消订阅
+pub fn subscribe() -> mpsc::Receiver<Event> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+pub fn emit(event: Event) {
+    log::info!("event: {:?}", event);
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| tx.send(event.clone()).is_ok());
+}
@@ -0,0 +1,72 @@
+use std::sync::{Mutex, Once, OnceLock};
+
+use crate::effect;
+use crate::loudness;
+use crate::mixer::{self, Mixer};
+use crate::trim;
+
+// audio
+// 录音/播放 采样率 HZ
+pub const SAMPLE_RATE: u32 = 16000;
+// paly gain
+pub static PLAY_GAIN: OnceLock<Mutex<u8>> = OnceLock::new();
+// 响度归一化设置，作用于 TTS 输出和音频库片段，在主音量之前
+pub static LOUDNESS: OnceLock<Mutex<loudness::Settings>> = OnceLock::new();
+pub const LOUDNESS_TARGET_DB: f32 = -20.0;
+pub const LOUDNESS_MAX_GAIN_DB: f32 = 12.0;
+// 合成语音的语速与音调
+pub static VOICE_EFFECT: OnceLock<Mutex<effect::Settings>> = OnceLock::new();
+// 合成语音首尾静音裁剪及播报间隔
+pub static SILENCE: OnceLock<Mutex<trim::Settings>> = OnceLock::new();
+pub const SILENCE_THRESHOLD_DB: f32 = -50.0;
+pub const SPEECH_GAP_MS: u32 = 300;
+// 扬声器输出订阅者缓存的帧数
+pub const OUTPUT_QUEUE_LEN: usize = 32;
+
+// mixer
+pub static MIXER: OnceLock<Mutex<Mixer>> = OnceLock::new();
+// 每次混音输出的采样点数 (16ms)
+pub const MIXER_FRAME_SAMPLES: usize = 256;
+// 语音播报时背景音源的增益
+pub const MIXER_DUCK_GAIN: f32 = 0.25;
+// 混音器音源数量上限 (包括语音音源)，超过时 POST /api/mixer/sources 返回 503
+pub const MIXER_MAX_SOURCES: usize = 5;
+// 背景音源的默认优先级
+pub const MIXER_DEFAULT_PRIORITY: u8 = 10;
+// 混音器空闲时音频线程检查新加入的背景音源的间隔
+pub const MIXER_IDLE_POLL_MS: u64 = 20;
+
+static INIT: Once = Once::new();
+
+// 初始化音频相关的全局状态，重复调用时忽略 (测试中每个用例都会调用)
+pub fn init() {
+    INIT.call_once(|| {
+        PLAY_GAIN.set(Mutex::new(1)).unwrap();
+        LOUDNESS
+            .set(Mutex::new(loudness::Settings {
+                enabled: true,
+                target_db: LOUDNESS_TARGET_DB,
+                max_gain_db: LOUDNESS_MAX_GAIN_DB,
+            }))
+            .unwrap();
+        VOICE_EFFECT
+            .set(Mutex::new(effect::Settings::default()))
+            .unwrap();
+        SILENCE
+            .set(Mutex::new(trim::Settings {
+                enabled: true,
+                threshold_db: SILENCE_THRESHOLD_DB,
+                gap_ms: SPEECH_GAP_MS,
+            }))
+            .unwrap();
+
+        let mut mixer = Mixer::new(MIXER_DUCK_GAIN);
+        mixer
+            .add(
+                mixer::Source::stream(mixer::SPEECH_ID, "speech", mixer::SPEECH_PRIORITY),
+                MIXER_MAX_SOURCES,
+            )
+            .unwrap();
+        MIXER.set(Mutex::new(mixer)).unwrap();
+    });
+}
@@ -0,0 +1,26 @@
+// 不依赖 esp-idf 的模块：音频管线、格式解析、协议与鉴权
+// 固件通过 etts-core 使用这些模块，主机上可直接运行测试
+
+pub mod adpcm;
+pub mod api;
+pub mod audio;
+pub mod auth;
+pub mod broker;
+pub mod cert;
+pub mod decoder;
+pub mod discovery;
+pub mod effect;
+pub mod event;
+pub mod global;
+pub mod line;
+pub mod loopback;
+pub mod loudness;
+pub mod markup;
+pub mod mixer;
+pub mod protocol;
+pub mod queue;
+pub mod sink;
+pub mod telemetry;
+pub mod tone;
+pub mod trim;
+pub mod wav;
@@ -0,0 +1,456 @@
+// TCP / UDP 文本协议：每行 (TCP) 或每个数据报 (UDP) 为一条消息，设备回复一行 ack
+//
+//   你好                   播报，回复 OK <id>
+//   !stop                  停止播报，回复 OK
+//   !vol 50                设置音量 0 ~ 100，回复 OK 50
+//   !!开头的文本           播报去掉第一个 ! 后的文本
+//   !auth Bearer 9f2c...   启用认证时先发送，值与 HTTP 请求头相同，回复 OK speak
+// 出错时回复 ERR <code> <message>，空行不回复
+// UDP 没有连接状态，启用认证时每个数据报以 "!auth ..." 行开头，其后为要执行的消息
+
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, ApiError, Device, Limits, SpeakRequest, VolumeRequest};
+use crate::auth::Scope;
+use crate::protocol::{self, Session};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub tcp: bool,
+    pub tcp_port: u16,
+    pub udp: bool,
+    pub udp_port: u16,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tcp: false,
+            tcp_port: 7000,
+            udp: false,
+            udp_port: 7001,
+        }
+    }
+}
+
+impl Settings {
+    // reserved 为 HTTP 服务器占用的端口
+    pub fn validate(&self, reserved: &[u16]) -> anyhow::Result<()> {
+        for port in [self.tcp_port, self.udp_port] {
+            if port == 0 || reserved.contains(&port) {
+                anyhow::bail!("port {} is not available", port);
+            }
+        }
+        if self.tcp && self.udp && self.tcp_port == self.udp_port {
+            anyhow::bail!("tcp and udp must use different ports");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Line {
+    Empty,
+    Speak(String),
+    Stop,
+    Volume(u8),
+    Auth(String),
+}
+
+pub fn parse(data: &[u8]) -> Result<Line, ApiError> {
+    let text = std::str::from_utf8(data).map_err(|_| ApiError::bad_request("invalid utf-8"))?;
+    let text = text.trim_end_matches(['\r', '\n']);
+    if text.trim().is_empty() {
+        return Ok(Line::Empty);
+    }
+    if let Some(escaped) = text.strip_prefix("!!") {
+        return Ok(Line::Speak(format!("!{}", escaped)));
+    }
+    let Some(command) = text.strip_prefix('!') else {
+        return Ok(Line::Speak(text.to_string()));
+    };
+
+    if let Some(authorization) = command
+        .strip_prefix("auth ")
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+    {
+        return Ok(Line::Auth(authorization.to_string()));
+    }
+
+    let mut args = command.split_whitespace();
+    match (args.next(), args.next(), args.next()) {
+        (Some("stop"), None, _) => Ok(Line::Stop),
+        (Some("vol"), Some(level), None) => level
+            .parse()
+            .map(Line::Volume)
+            .map_err(|_| ApiError::bad_request(format!("invalid volume: {}", level))),
+        (Some("vol"), ..) => Err(ApiError::bad_request("usage: !vol 0~100")),
+        (Some("auth"), None, _) => Err(ApiError::bad_request("usage: !auth <authorization>")),
+        _ => Err(ApiError::bad_request(format!("unknown command: {}", text))),
+    }
+}
+
+// 执行一条消息，返回回复的内容 (不含换行)，空行返回 None
+// session 为该连接的认证状态，与 WebSocket 相同，见 protocol.rs
+pub fn handle(
+    device: &mut impl Device,
+    limits: &Limits,
+    session: &mut Session,
+    data: &[u8],
+) -> Option<String> {
+    let result = parse(data).and_then(|line| execute(device, limits, session, line));
+    match result {
+        Ok(Some(ack)) => Some(ack),
+        Ok(None) => None,
+        Err(e) => {
+            log::warn!("line command error: {:?}", e);
+            Some(format!("ERR {} {}", e.code, e.message))
+        }
+    }
+}
+
+// 执行一个 UDP 数据报，开头的 "!auth ..." 行只对该数据报有效
+pub fn handle_datagram(device: &mut impl Device, limits: &Limits, data: &[u8]) -> Option<String> {
+    let mut session = protocol::connect(device);
+    if !data.starts_with(b"!auth ") {
+        return handle(device, limits, &mut session, data);
+    }
+    let (auth, rest) = match data.iter().position(|&b| b == b'\n') {
+        Some(i) => (&data[..i], &data[i + 1..]),
+        None => (data, &[][..]),
+    };
+    let reply = handle(device, limits, &mut session, auth);
+    if session.scope.is_none() || rest.is_empty() {
+        return reply;
+    }
+    handle(device, limits, &mut session, rest)
+}
+
+fn execute(
+    device: &mut impl Device,
+    limits: &Limits,
+    session: &mut Session,
+    line: Line,
+) -> Result<Option<String>, ApiError> {
+    if !matches!(line, Line::Empty | Line::Auth(_)) {
+        protocol::require(session.scope, Scope::Speak)?;
+    }
+    let ack = match line {
+        Line::Empty => return Ok(None),
+        Line::Speak(text) => {
+            let request = SpeakRequest {
+                text,
+                pre: None,
+                post: None,
+                priority: 0,
+            };
+            format!("OK {}", api::speak(device, limits, request)?)
+        }
+        Line::Stop => {
+            device.stop();
+            "OK".to_string()
+        }
+        Line::Volume(level) => {
+            let request = VolumeRequest {
+                level: Some(level),
+                op: None,
+            };
+            format!("OK {}", api::set_volume(device, &request)?)
+        }
+        Line::Auth(authorization) => {
+            // 认证失败时撤销之前的权限
+            *session = Session::default();
+            let scope = device.authenticate(Some(&authorization))?;
+            *session = Session {
+                authorization: Some(authorization),
+                scope: Some(scope),
+            };
+            format!("OK {}", scope)
+        }
+    };
+    Ok(Some(ack))
+}
+
+// 把 TCP 数据按换行切分，超过 max_len 的行在下一个换行前的内容都被丢弃
+#[derive(Debug)]
+pub struct Lines {
+    buf: Vec<u8>,
+    max_len: usize,
+    overflow: bool,
+}
+
+impl Lines {
+    pub fn new(max_len: usize) -> Self {
+        Lines {
+            buf: Vec::new(),
+            max_len,
+            overflow: false,
+        }
+    }
+
+    // 返回 data 中完整的行，超长的行返回 Err
+    pub fn push(&mut self, data: &[u8]) -> Vec<Result<Vec<u8>, ApiError>> {
+        let mut lines = Vec::new();
+        for &byte in data {
+            if byte == b'\n' {
+                if self.overflow {
+                    self.overflow = false;
+                    lines.push(Err(ApiError::new(
+                        413,
+                        "payload_too_large",
+                        format!("line exceeds {} bytes", self.max_len),
+                    )));
+                } else {
+                    lines.push(Ok(std::mem::take(&mut self.buf)));
+                }
+                continue;
+            }
+            if self.overflow {
+                continue;
+            }
+            if self.buf.len() >= self.max_len {
+                self.buf.clear();
+                self.overflow = true;
+                continue;
+            }
+            self.buf.push(byte);
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::fake::{FakeDevice, LIMITS};
+    use crate::auth::{Credential, Kind};
+
+    fn send(device: &mut FakeDevice, session: &mut Session, data: &str) -> Option<String> {
+        handle(device, &LIMITS, session, data.as_bytes())
+    }
+
+    // 启用认证，speak token 为 t，admin token 为 a
+    fn protected() -> FakeDevice {
+        let mut device = FakeDevice::default();
+        let admin = Credential::new("admin", Kind::Token, Scope::Admin, "a", b"s", 1);
+        device.store.add(admin, 4).unwrap();
+        let speak = Credential::new("tv", Kind::Token, Scope::Speak, "t", b"s", 1);
+        device.store.add(speak, 4).unwrap();
+        device
+    }
+
+    #[test]
+    fn parse_lines() {
+        let cases = [
+            ("你好", Line::Speak("你好".to_string())),
+            ("你好\r\n", Line::Speak("你好".to_string())),
+            ("  hi  \n", Line::Speak("  hi  ".to_string())),
+            ("", Line::Empty),
+            (" \r\n", Line::Empty),
+            ("!stop", Line::Stop),
+            ("!stop\r\n", Line::Stop),
+            ("!vol 50", Line::Volume(50)),
+            ("!vol  7 \r", Line::Volume(7)),
+            ("!!stop", Line::Speak("!stop".to_string())),
+            ("!!", Line::Speak("!".to_string())),
+            ("a!stop", Line::Speak("a!stop".to_string())),
+            ("!auth Bearer x y\r\n", Line::Auth("Bearer x y".to_string())),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(parse(data.as_bytes()).unwrap(), expected, "{:?}", data);
+        }
+
+        for data in [
+            "!",
+            "!Stop",
+            "!stop now",
+            "!vol",
+            "!vol 1 2",
+            "!vol x",
+            "!vol 256",
+            "!auth",
+            "!auth  ",
+        ] {
+            let e = parse(data.as_bytes()).unwrap_err();
+            assert_eq!(e.code, "bad_request", "{:?}", data);
+        }
+        assert!(parse(&[0xff, 0xfe]).is_err());
+    }
+
+    #[test]
+    fn commands() {
+        let mut device = FakeDevice::default();
+        let mut session = protocol::connect(&device);
+
+        assert_eq!(
+            send(&mut device, &mut session, "你好\r\n").as_deref(),
+            Some("OK 1")
+        );
+        assert_eq!(
+            send(&mut device, &mut session, "!!hi").as_deref(),
+            Some("OK 2")
+        );
+        assert_eq!(device.spoken[1].text, "!hi");
+        assert_eq!(
+            send(&mut device, &mut session, "!vol 50").as_deref(),
+            Some("OK 50")
+        );
+        assert_eq!(device.volume, 50);
+        assert_eq!(
+            send(&mut device, &mut session, "!stop").as_deref(),
+            Some("OK")
+        );
+        assert_eq!(device.stopped, 1);
+        assert_eq!(send(&mut device, &mut session, "\r\n"), None);
+
+        let reply = send(&mut device, &mut session, "!vol 101").unwrap();
+        assert!(reply.starts_with("ERR bad_request "), "{}", reply);
+        let reply = send(&mut device, &mut session, "!dance").unwrap();
+        assert_eq!(reply, "ERR bad_request unknown command: !dance");
+        let reply = send(&mut device, &mut session, &"长".repeat(20)).unwrap();
+        assert!(reply.starts_with("ERR "), "{}", reply);
+        let reply = send(&mut device, &mut session, "a\0b").unwrap();
+        assert!(reply.starts_with("ERR bad_request "), "{}", reply);
+        assert_eq!(device.spoken.len(), 2);
+    }
+
+    #[test]
+    fn auth() {
+        let mut device = protected();
+        let mut session = protocol::connect(&device);
+
+        for line in ["hi", "!stop", "!vol 5"] {
+            let reply = send(&mut device, &mut session, line).unwrap();
+            assert!(reply.starts_with("ERR unauthorized "), "{}", reply);
+        }
+        let reply = send(&mut device, &mut session, "!auth Bearer x").unwrap();
+        assert!(reply.starts_with("ERR unauthorized "), "{}", reply);
+
+        assert_eq!(
+            send(&mut device, &mut session, "!auth Bearer t\r\n").as_deref(),
+            Some("OK speak")
+        );
+        assert_eq!(
+            send(&mut device, &mut session, "hi").as_deref(),
+            Some("OK 1")
+        );
+        assert_eq!(
+            send(&mut device, &mut session, "!stop").as_deref(),
+            Some("OK")
+        );
+
+        // 认证失败后撤销之前的权限
+        send(&mut device, &mut session, "!auth Bearer x");
+        assert!(send(&mut device, &mut session, "!stop")
+            .unwrap()
+            .starts_with("ERR unauthorized "));
+
+        // 删除凭据后重新校验
+        send(&mut device, &mut session, "!auth Bearer t");
+        device.store.remove("tv").unwrap();
+        session.refresh(|authorization| device.store.authenticate(authorization));
+        assert!(send(&mut device, &mut session, "hi")
+            .unwrap()
+            .starts_with("ERR unauthorized "));
+        assert_eq!((device.spoken.len(), device.stopped), (1, 1));
+    }
+
+    #[test]
+    fn datagrams() {
+        let mut device = FakeDevice::default();
+        let reply = handle_datagram(&mut device, &LIMITS, b"hi\n");
+        assert_eq!(reply.as_deref(), Some("OK 1"));
+
+        let mut device = protected();
+        let reply = handle_datagram(&mut device, &LIMITS, b"hi").unwrap();
+        assert!(reply.starts_with("ERR unauthorized "), "{}", reply);
+        let reply = handle_datagram(&mut device, &LIMITS, b"!auth Bearer x\nhi").unwrap();
+        assert!(reply.starts_with("ERR unauthorized "), "{}", reply);
+        let reply = handle_datagram(&mut device, &LIMITS, b"!auth Bearer t\r\n!vol 9\r\n");
+        assert_eq!(reply.as_deref(), Some("OK 9"));
+        let reply = handle_datagram(&mut device, &LIMITS, b"!auth Bearer a");
+        assert_eq!(reply.as_deref(), Some("OK admin"));
+        // 认证只对所在的数据报有效
+        let reply = handle_datagram(&mut device, &LIMITS, b"!stop").unwrap();
+        assert!(reply.starts_with("ERR unauthorized "), "{}", reply);
+        assert!(device.spoken.is_empty());
+        assert_eq!((device.volume, device.stopped), (9, 0));
+    }
+
+    #[test]
+    fn split_lines() {
+        let mut lines = Lines::new(8);
+        assert!(lines.push(b"ab").is_empty());
+        assert!(lines.push(b"c").is_empty());
+        let out = lines.push(b"d\r\nef\n\ngh");
+        let out: Vec<Vec<u8>> = out.into_iter().map(Result::unwrap).collect();
+        assert_eq!(out, [b"abcd\r".to_vec(), b"ef".to_vec(), b"".to_vec()]);
+        let out = lines.push(b"\n");
+        assert_eq!(out[0].as_ref().unwrap(), b"gh");
+
+        // 一行跨越多次读取且按字节拆开的 UTF-8 也能完整拼接
+        let text = "你好".as_bytes();
+        assert!(lines.push(&text[..2]).is_empty());
+        let out = lines.push(&[&text[2..], b"\n"].concat());
+        assert_eq!(
+            parse(out[0].as_ref().unwrap()).unwrap(),
+            Line::Speak("你好".to_string())
+        );
+    }
+
+    #[test]
+    fn oversize_lines() {
+        let mut lines = Lines::new(4);
+        assert_eq!(lines.push(b"1234\n")[0].as_ref().unwrap(), b"1234");
+
+        // 超长的行在下一个换行前的内容都被丢弃，之后的行不受影响
+        assert!(lines.push(b"12345").is_empty());
+        assert!(lines.push(b"678").is_empty());
+        let out = lines.push(b"9\nok\n");
+        assert_eq!(out.len(), 2);
+        let e = out[0].as_ref().unwrap_err();
+        assert_eq!((e.status, e.code), (413, "payload_too_large"));
+        assert_eq!(e.message, "line exceeds 4 bytes");
+        assert_eq!(out[1].as_ref().unwrap(), b"ok");
+    }
+
+    #[test]
+    fn validate_ports() {
+        let reserved = LIMITS.reserved_ports;
+        let settings = Settings {
+            tcp: true,
+            udp: true,
+            ..Default::default()
+        };
+        assert!(settings.validate(reserved).is_ok());
+
+        for port in [0, 80, 443, 32768, 32769] {
+            let tcp = Settings {
+                tcp_port: port,
+                ..settings
+            };
+            assert!(tcp.validate(reserved).is_err(), "{}", port);
+            let udp = Settings {
+                udp_port: port,
+                ..settings
+            };
+            assert!(udp.validate(reserved).is_err(), "{}", port);
+        }
+        assert!(Settings {
+            tcp_port: 8080,
+            ..settings
+        }
+        .validate(&[])
+        .is_ok());
+
+        // 同时启用时 TCP 和 UDP 不能使用相同的端口
+        let same = Settings {
+            udp_port: settings.tcp_port,
+            ..settings
+        };
+        assert!(same.validate(reserved).is_err());
+        assert!(Settings { udp: false, ..same }.validate(reserved).is_ok());
+    }
+}
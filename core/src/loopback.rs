@@ -0,0 +1,76 @@
+// 扬声器 / 麦克风回环自检
+// 播放一段单频测试音 (tone::sine)，同时录音，用 Goertzel 算法检测录音中的测试音
+
+use serde::Serialize;
+
+// 分析窗口长度 (ms)
+const BLOCK_MS: u32 = 10;
+// 判定通过的最小信噪比与测试音电平
+const MIN_SNR_DB: f32 = 20.0;
+const MIN_TONE_DB: f32 = -60.0;
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub passed: bool,
+    // 背景噪声与测试音在测试频率上的电平 (dBFS)
+    pub noise_db: f32,
+    pub tone_db: f32,
+    pub snr_db: f32,
+    // 从开始录音到检测到测试音的时间
+    pub latency_ms: Option<u32>,
+}
+
+// 单个频率的幅度 (dBFS)
+fn goertzel_db(block: &[i16], freq: u32, sample_rate: u32) -> f32 {
+    let w = 2.0 * std::f32::consts::PI * freq as f32 / sample_rate as f32;
+    let coeff = 2.0 * w.cos();
+    let (mut s1, mut s2) = (0f32, 0f32);
+    for sample in block {
+        let s = *sample as f32 + coeff * s1 - s2;
+        s2 = s1;
+        s1 = s;
+    }
+    let power = (s1 * s1 + s2 * s2 - coeff * s1 * s2).max(0.0);
+    let amplitude = 2.0 * power.sqrt() / block.len() as f32;
+    20.0 * (amplitude / 32768.0).max(1e-6).log10()
+}
+
+pub fn analyze(recorded: &[i16], freq: u32, sample_rate: u32) -> Report {
+    let block_len = (sample_rate * BLOCK_MS / 1000) as usize;
+    let levels: Vec<f32> = recorded
+        .chunks_exact(block_len)
+        .map(|block| goertzel_db(block, freq, sample_rate))
+        .collect();
+    if levels.is_empty() {
+        return Report {
+            passed: false,
+            noise_db: 0.0,
+            tone_db: 0.0,
+            snr_db: 0.0,
+            latency_ms: None,
+        };
+    }
+
+    let mut sorted = levels.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_db = sorted[sorted.len() / 10];
+    let tone_db = sorted[sorted.len() * 9 / 10];
+    let snr_db = tone_db - noise_db;
+    let passed = snr_db >= MIN_SNR_DB && tone_db >= MIN_TONE_DB;
+
+    // 第一个超过噪声与测试音中点的窗口
+    let threshold = noise_db + snr_db / 2.0;
+    let latency_ms = levels
+        .iter()
+        .position(|l| *l > threshold)
+        .filter(|_| passed)
+        .map(|i| i as u32 * BLOCK_MS);
+
+    Report {
+        passed,
+        noise_db,
+        tone_db,
+        snr_db,
+        latency_ms,
+    }
+}
@@ -0,0 +1,90 @@
+// 响度归一化：按段统计非静音部分的均方根电平，自动增益到目标响度
+// 流式处理，每个文本段 / 音频片段使用一个新的 Normalizer
+
+use serde::{Deserialize, Serialize};
+
+// 低于该电平的块视为静音，不参与响度统计 (dBFS)
+const GATE_DB: f32 = -50.0;
+// 每块增益变化的上限 (dB)，避免音量突变
+const MAX_STEP_DB: f32 = 1.5;
+// 增益后的峰值上限
+const PEAK_LIMIT: f32 = 30000.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Settings {
+    pub enabled: bool,
+    // 目标响度 (dBFS RMS)
+    pub target_db: f32,
+    // 最大提升量 (dB)，避免把噪声放大
+    pub max_gain_db: f32,
+}
+
+impl Settings {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(-40.0..=-6.0).contains(&self.target_db) || !(0.0..=30.0).contains(&self.max_gain_db) {
+            anyhow::bail!("invalid loudness settings: {:?}", self);
+        }
+        Ok(())
+    }
+}
+
+pub struct Normalizer {
+    settings: Settings,
+    // 非静音块的平方和与采样数
+    sum_sq: f64,
+    count: u64,
+    // 当前增益 (dB)，还没有统计数据时为 None
+    gain_db: Option<f32>,
+}
+
+impl Normalizer {
+    pub fn new(settings: Settings) -> Self {
+        Normalizer {
+            settings,
+            sum_sq: 0.0,
+            count: 0,
+            gain_db: None,
+        }
+    }
+
+    // 目前为止的响度 (dBFS)，全部为静音时返回 None
+    pub fn loudness_db(&self) -> Option<f32> {
+        (self.count > 0).then(|| mean_square_db(self.sum_sq / self.count as f64))
+    }
+
+    pub fn process(&mut self, pcm: &mut [i16]) {
+        if !self.settings.enabled || pcm.is_empty() {
+            return;
+        }
+
+        let sum_sq: f64 = pcm.iter().map(|s| (*s as f64) * (*s as f64)).sum();
+        if mean_square_db(sum_sq / pcm.len() as f64) > GATE_DB {
+            self.sum_sq += sum_sq;
+            self.count += pcm.len() as u64;
+        }
+
+        let Some(loudness) = self.loudness_db() else {
+            return;
+        };
+        let target = (self.settings.target_db - loudness).min(self.settings.max_gain_db);
+        // 第一块直接使用目标增益，之后平滑变化
+        let gain_db = match self.gain_db {
+            Some(gain_db) => gain_db + (target - gain_db).clamp(-MAX_STEP_DB, MAX_STEP_DB),
+            None => target,
+        };
+        self.gain_db = Some(gain_db);
+
+        let peak = pcm.iter().map(|s| (*s as f32).abs()).fold(0.0, f32::max);
+        let mut gain = 10f32.powf(gain_db / 20.0);
+        if peak * gain > PEAK_LIMIT {
+            gain = PEAK_LIMIT / peak;
+        }
+        for sample in pcm.iter_mut() {
+            *sample = (*sample as f32 * gain) as i16;
+        }
+    }
+}
+
+fn mean_square_db(mean_square: f64) -> f32 {
+    (10.0 * (mean_square.max(1e-3) / (32768.0 * 32768.0)).log10()) as f32
+}
@@ -0,0 +1,38 @@
+// TTS 文本标记
+// 文本中的 [clip:名称] 会被替换为对应的音频片段，例如 "[clip:ding]请注意"
+
+const CLIP_OPEN: &str = "[clip:";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Segment<'a> {
+    Text(&'a str),
+    Clip(&'a str),
+}
+
+pub fn parse(text: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find(CLIP_OPEN) {
+        let after = &rest[start + CLIP_OPEN.len()..];
+        let Some(end) = after.find(']') else {
+            break;
+        };
+
+        push_text(&mut segments, &rest[..start]);
+        let name = after[..end].trim();
+        if !name.is_empty() {
+            segments.push(Segment::Clip(name));
+        }
+        rest = &after[end + 1..];
+    }
+    push_text(&mut segments, rest);
+
+    segments
+}
+
+fn push_text<'a>(segments: &mut Vec<Segment<'a>>, text: &'a str) {
+    if !text.trim().is_empty() {
+        segments.push(Segment::Text(text));
+    }
+}
@@ -0,0 +1,515 @@
+// 多路音频混音器
+//
+// 每路音源有独立的增益和优先级，当语音播报进行中或高优先级音源有声音时，
+// 低优先级音源（例如背景音乐）会被压低 (ducking)。各路叠加后经过峰值限幅输出。
+
+use std::collections::VecDeque;
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::decoder;
+
+// 语音播报固定使用的音源 ID 和优先级
+pub const SPEECH_ID: u32 = 0;
+pub const SPEECH_PRIORITY: u8 = u8::MAX;
+
+// 限幅阈值，保留约 1dB 余量
+const LIMIT: i32 = 29000;
+// 限幅器每帧恢复的增益
+const LIMITER_RELEASE: f32 = 0.02;
+// ducking 每个采样变化的增益，约 50ms 完成切换
+const DUCK_STEP: f32 = 1.0 / 800.0;
+
+#[derive(Debug)]
+enum Kind {
+    // 由外部持续写入的数据流
+    Stream(VecDeque<i16>),
+    // 完整加载的音频，可循环
+    Buffer {
+        pcm: Vec<i16>,
+        pos: usize,
+        looping: bool,
+    },
+    // 边播放边解码的音频，例如音频库片段，内存占用与文件大小无关
+    Decoder(Decoder),
+}
+
+type Open = Box<dyn FnMut() -> anyhow::Result<decoder::Stream> + Send>;
+
+// 每次解码一个 block / frame，循环播放时重新打开
+struct Decoder {
+    stream: decoder::Stream,
+    open: Open,
+    buf: VecDeque<i16>,
+    looping: bool,
+    // 重新打开后还没有解码出数据，用于避免空文件无限循环
+    rewound: bool,
+    done: bool,
+}
+
+impl fmt::Debug for Decoder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("buffered", &self.buf.len())
+            .field("looping", &self.looping)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+impl Decoder {
+    fn next_sample(&mut self) -> Option<i16> {
+        while self.buf.is_empty() && !self.done {
+            self.fill();
+        }
+        self.buf.pop_front()
+    }
+
+    fn fill(&mut self) {
+        match self.stream.next_chunk() {
+            Ok(Some(pcm)) => {
+                if !pcm.is_empty() {
+                    self.rewound = false;
+                }
+                self.buf.extend(pcm);
+            }
+            Ok(None) if self.looping && !self.rewound => match (self.open)() {
+                Ok(stream) => {
+                    self.stream = stream;
+                    self.rewound = true;
+                }
+                Err(e) => {
+                    log::warn!("mixer source reopen error: {:?}", e);
+                    self.done = true;
+                }
+            },
+            Ok(None) => self.done = true,
+            Err(e) => {
+                log::warn!("mixer source decode error: {:?}", e);
+                self.done = true;
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Source {
+    id: u32,
+    name: String,
+    priority: u8,
+    gain: f32,
+    duck: f32,
+    kind: Kind,
+}
+
+impl Source {
+    pub fn stream(id: u32, name: &str, priority: u8) -> Self {
+        Self::new(id, name, priority, Kind::Stream(VecDeque::new()))
+    }
+
+    pub fn buffer(id: u32, name: &str, priority: u8, pcm: Vec<i16>, looping: bool) -> Self {
+        Self::new(
+            id,
+            name,
+            priority,
+            Kind::Buffer {
+                pcm,
+                pos: 0,
+                looping,
+            },
+        )
+    }
+
+    // open 打开音频流，添加时调用一次，循环播放到结尾时再次调用
+    pub fn decoder(
+        id: u32,
+        name: &str,
+        priority: u8,
+        mut open: impl FnMut() -> anyhow::Result<decoder::Stream> + Send + 'static,
+        looping: bool,
+    ) -> anyhow::Result<Self> {
+        let stream = open()?;
+        Ok(Self::new(
+            id,
+            name,
+            priority,
+            Kind::Decoder(Decoder {
+                stream,
+                open: Box::new(open),
+                buf: VecDeque::new(),
+                looping,
+                rewound: false,
+                done: false,
+            }),
+        ))
+    }
+
+    fn new(id: u32, name: &str, priority: u8, kind: Kind) -> Self {
+        Source {
+            id,
+            name: name.to_string(),
+            priority,
+            gain: 1.0,
+            duck: 1.0,
+            kind,
+        }
+    }
+
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = gain.max(0.0);
+        self
+    }
+
+    // 是否还有待播放的数据
+    fn is_active(&self) -> bool {
+        match &self.kind {
+            Kind::Stream(buf) => !buf.is_empty(),
+            Kind::Buffer { pcm, pos, looping } => !pcm.is_empty() && (*looping || *pos < pcm.len()),
+            Kind::Decoder(decoder) => !decoder.done || !decoder.buf.is_empty(),
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        !matches!(&self.kind, Kind::Stream(_)) && !self.is_active()
+    }
+
+    fn is_looping(&self) -> bool {
+        match &self.kind {
+            Kind::Stream(_) => false,
+            Kind::Buffer { looping, .. } => *looping,
+            Kind::Decoder(decoder) => decoder.looping,
+        }
+    }
+
+    fn next_sample(&mut self) -> Option<i16> {
+        match &mut self.kind {
+            Kind::Stream(buf) => buf.pop_front(),
+            Kind::Buffer { pcm, pos, looping } => {
+                if *pos >= pcm.len() {
+                    if !*looping || pcm.is_empty() {
+                        return None;
+                    }
+                    *pos = 0;
+                }
+                let sample = pcm[*pos];
+                *pos += 1;
+                Some(sample)
+            }
+            Kind::Decoder(decoder) => decoder.next_sample(),
+        }
+    }
+}
+
+// 音源数量已达上限
+#[derive(Debug)]
+pub struct Full;
+
+impl std::fmt::Display for Full {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "too many mixer sources")
+    }
+}
+
+impl std::error::Error for Full {}
+
+#[derive(Debug, Serialize)]
+pub struct SourceInfo {
+    pub id: u32,
+    pub name: String,
+    pub priority: u8,
+    pub gain: f32,
+    pub active: bool,
+    pub looping: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Info {
+    pub duck_gain: f32,
+    pub sources: Vec<SourceInfo>,
+}
+
+#[derive(Debug)]
+pub struct Mixer {
+    sources: Vec<Source>,
+    // 被压低时低优先级音源的增益
+    duck_gain: f32,
+    // 限幅器当前增益
+    limiter: f32,
+    // 语音播报进行中 (Begin 到 End)，播报中语音数据暂时断流时背景音源也保持压低
+    ducking: bool,
+    next_id: u32,
+}
+
+impl Mixer {
+    pub fn new(duck_gain: f32) -> Self {
+        Mixer {
+            sources: Vec::new(),
+            duck_gain: duck_gain.clamp(0.0, 1.0),
+            limiter: 1.0,
+            ducking: false,
+            next_id: SPEECH_ID + 1,
+        }
+    }
+
+    // 分配一个新的音源 ID
+    pub fn alloc_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // 同 ID 的音源被替换，max_len 为包括语音音源在内的音源数量上限
+    pub fn add(&mut self, source: Source, max_len: usize) -> Result<(), Full> {
+        let others = self.sources.iter().filter(|s| s.id != source.id).count();
+        if others >= max_len {
+            return Err(Full);
+        }
+        self.remove(source.id);
+        self.sources.push(source);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.sources.len();
+        self.sources.retain(|s| s.id != id);
+        self.sources.len() != len
+    }
+
+    pub fn set_gain(&mut self, id: u32, gain: f32) -> bool {
+        match self.sources.iter_mut().find(|s| s.id == id) {
+            Some(source) => {
+                source.gain = gain.max(0.0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn set_duck_gain(&mut self, duck_gain: f32) {
+        self.duck_gain = duck_gain.clamp(0.0, 1.0);
+    }
+
+    pub fn set_ducking(&mut self, ducking: bool) {
+        self.ducking = ducking;
+    }
+
+    // 向数据流音源写入采样
+    pub fn push(&mut self, id: u32, pcm: &[i16]) {
+        if let Some(Source {
+            kind: Kind::Stream(buf),
+            ..
+        }) = self.sources.iter_mut().find(|s| s.id == id)
+        {
+            buf.extend(pcm);
+        }
+    }
+
+    // 清空数据流音源
+    pub fn clear(&mut self, id: u32) {
+        if let Some(Source {
+            kind: Kind::Stream(buf),
+            ..
+        }) = self.sources.iter_mut().find(|s| s.id == id)
+        {
+            buf.clear();
+        }
+    }
+
+    // 数据流音源中已缓冲的采样数
+    pub fn buffered(&self, id: u32) -> usize {
+        match self.sources.iter().find(|s| s.id == id) {
+            Some(Source {
+                kind: Kind::Stream(buf),
+                ..
+            }) => buf.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.sources.iter().any(|s| s.is_active())
+    }
+
+    pub fn info(&self) -> Info {
+        Info {
+            duck_gain: self.duck_gain,
+            sources: self
+                .sources
+                .iter()
+                .map(|s| SourceInfo {
+                    id: s.id,
+                    name: s.name.clone(),
+                    priority: s.priority,
+                    gain: s.gain,
+                    active: s.is_active(),
+                    looping: s.is_looping(),
+                })
+                .collect(),
+        }
+    }
+
+    // 混合一帧输出，master_gain 为最终音量
+    // 返回主音量增益后超出 16 位范围、由限幅器压低的采样数
+    pub fn mix(&mut self, out: &mut [i16], master_gain: f32) -> usize {
+        // 有声音的最高优先级，低于它的音源被压低
+        // 数据流音源 (语音) 按播报的开始和结束计算，不看缓冲区是否有数据
+        let top = self
+            .sources
+            .iter()
+            .filter(|s| match s.kind {
+                Kind::Stream(_) => self.ducking,
+                _ => s.is_active(),
+            })
+            .map(|s| s.priority)
+            .max()
+            .unwrap_or(0);
+
+        let mut acc = vec![0f32; out.len()];
+        for source in self.sources.iter_mut() {
+            let target = if source.priority < top {
+                self.duck_gain
+            } else {
+                1.0
+            };
+            for sample in acc.iter_mut() {
+                // 每个采样平滑逼近目标增益，避免音量突变产生爆音
+                source.duck += (target - source.duck).clamp(-DUCK_STEP, DUCK_STEP);
+                let Some(s) = source.next_sample() else {
+                    break;
+                };
+                *sample += s as f32 * source.gain * source.duck;
+            }
+        }
+
+        // 播放结束的非循环音源自动移除
+        self.sources.retain(|s| !s.is_finished());
+
+        // 峰值限幅：超过阈值立即降低增益，之后缓慢恢复
+        let peak = acc.iter().fold(0f32, |m, s| m.max((s * master_gain).abs()));
+        self.limiter = (self.limiter + LIMITER_RELEASE).min(1.0);
+        if peak * self.limiter > LIMIT as f32 {
+            self.limiter = LIMIT as f32 / peak;
+        }
+
+        let gain = master_gain * self.limiter;
+        for (o, s) in out.iter_mut().zip(acc.iter()) {
+            *o = ((s * gain) as i32).clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+        }
+
+        acc.iter()
+            .filter(|s| (*s * master_gain).abs() > i16::MAX as f32)
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixer() -> Mixer {
+        let mut mixer = Mixer::new(0.25);
+        mixer
+            .add(Source::stream(SPEECH_ID, "speech", SPEECH_PRIORITY), 3)
+            .unwrap();
+        mixer
+    }
+
+    #[test]
+    fn max_sources() {
+        let mut mixer = mixer();
+        for _ in 0..2 {
+            let id = mixer.alloc_id();
+            mixer
+                .add(Source::buffer(id, "bgm", 10, vec![1; 16], true), 3)
+                .unwrap();
+        }
+        let id = mixer.alloc_id();
+        assert!(mixer
+            .add(Source::buffer(id, "bgm", 10, vec![1; 16], true), 3)
+            .is_err());
+        assert_eq!(mixer.info().sources.len(), 3);
+
+        // 替换同 ID 的音源不受上限影响
+        mixer
+            .add(Source::buffer(id - 1, "bgm", 20, vec![1; 16], true), 3)
+            .unwrap();
+        assert!(mixer.remove(id - 1));
+        mixer
+            .add(Source::buffer(id, "bgm", 10, vec![1; 16], true), 3)
+            .unwrap();
+    }
+
+    #[test]
+    fn ducking_follows_speech() {
+        let mut mixer = mixer();
+        let id = mixer.alloc_id();
+        mixer
+            .add(Source::buffer(id, "bgm", 10, vec![1000; 16], true), 3)
+            .unwrap();
+        let mut frame = vec![0i16; 1024];
+
+        // 播报开始后即使语音数据未送达也保持压低
+        mixer.set_ducking(true);
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame[1023], 250);
+        mixer.push(SPEECH_ID, &[100; 512]);
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame[0], 350);
+        assert_eq!(frame[1023], 250);
+
+        // 播报结束后恢复
+        mixer.set_ducking(false);
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame[1023], 1000);
+    }
+
+    fn pcm() -> Vec<i16> {
+        (1..=300).collect()
+    }
+
+    #[test]
+    fn decoder_source() {
+        let mut mixer = mixer();
+        let id = mixer.alloc_id();
+        let open = || Ok(decoder::Stream::from_pcm(pcm(), 16000));
+        mixer
+            .add(Source::decoder(id, "bgm", 10, open, false).unwrap(), 3)
+            .unwrap();
+        let mut frame = vec![0i16; 256];
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame, pcm()[..256]);
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame[..44], pcm()[256..]);
+        assert!(frame[44..].iter().all(|s| *s == 0));
+
+        // 播放结束后自动移除
+        assert!(!mixer.is_active());
+        assert_eq!(mixer.info().sources.len(), 1);
+    }
+
+    #[test]
+    fn decoder_source_loop() {
+        let mut mixer = mixer();
+        let id = mixer.alloc_id();
+        let open = || Ok(decoder::Stream::from_pcm(pcm(), 16000));
+        mixer
+            .add(Source::decoder(id, "bgm", 10, open, true).unwrap(), 3)
+            .unwrap();
+
+        // 到结尾时重新打开，从头播放
+        let mut frame = vec![0i16; 1000];
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(frame, pcm().repeat(4)[..1000]);
+        assert!(mixer.info().sources[1].looping);
+
+        // 空音频循环时直接结束
+        let id = mixer.alloc_id();
+        let open = || Ok(decoder::Stream::from_pcm(Vec::new(), 16000));
+        mixer
+            .add(Source::decoder(id, "empty", 10, open, true).unwrap(), 3)
+            .unwrap();
+        mixer.mix(&mut frame, 1.0);
+        assert_eq!(mixer.info().sources.len(), 2);
+    }
+}
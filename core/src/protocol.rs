@@ -0,0 +1,454 @@
+// WebSocket 消息编解码：客户端发送与 REST API 相同的命令，设备推送事件
+//
+// 客户端 -> 设备：
+//   {"type": "speak", "text": "你好", "seq": 1}，可带 pre / post 提示音
+//   {"type": "stop"}
+//   {"type": "volume", "level": 50} 或 {"type": "volume", "op": "inc"}
+//   {"type": "auth", "authorization": "Bearer ..."}，启用认证时需先发送，值与 HTTP 请求头相同
+// 设备 -> 客户端：
+//   {"type": "ack", "seq": 1, "id": 5}，音量命令返回 "level"，认证返回 "scope"
+//   {"type": "error", "seq": 1, "code": "invalid_json", "message": "..."}
+//   事件，例如 {"type": "started", "id": 5}、{"type": "volume", "level": 3}
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::api::{self, ApiError, Device, Limits, SpeakRequest, VolumeRequest};
+use crate::auth::{Denied, Scope};
+use crate::event::Event;
+
+#[derive(Debug)]
+pub enum Command {
+    Speak(SpeakRequest),
+    Stop,
+    Volume(VolumeRequest),
+    Auth(AuthRequest),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthRequest {
+    pub authorization: String,
+}
+
+// seq 由客户端指定，原样带回到 ack / error 中
+#[derive(Debug)]
+pub struct Message {
+    pub seq: Option<u64>,
+    pub command: Command,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply<'a> {
+    Ack {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<u32>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        level: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scope: Option<Scope>,
+    },
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        code: &'a str,
+        message: &'a str,
+    },
+}
+
+// 解码失败时尽量取出 seq，便于客户端对应到请求
+pub fn decode(data: &[u8]) -> Result<Message, (Option<u64>, ApiError)> {
+    let mut object: Map<String, Value> =
+        serde_json::from_slice(data).map_err(|e| (None, ApiError::invalid_json(e.to_string())))?;
+
+    let seq = match object.remove("seq") {
+        None | Some(Value::Null) => None,
+        Some(value) => Some(
+            value
+                .as_u64()
+                .ok_or_else(|| (None, ApiError::bad_request("seq must be an integer")))?,
+        ),
+    };
+    let error = |e: ApiError| (seq, e);
+
+    let kind = match object.remove("type") {
+        Some(Value::String(kind)) => kind,
+        _ => return Err(error(ApiError::bad_request("type is required"))),
+    };
+    let args = Value::Object(object);
+    let parse = |e: serde_json::Error| error(ApiError::invalid_json(e.to_string()));
+
+    let command = match kind.as_str() {
+        "speak" => Command::Speak(serde_json::from_value(args).map_err(parse)?),
+        "stop" => Command::Stop,
+        "volume" => Command::Volume(serde_json::from_value(args).map_err(parse)?),
+        "auth" => Command::Auth(serde_json::from_value(args).map_err(parse)?),
+        _ => {
+            return Err(error(ApiError::bad_request(format!(
+                "unknown type: {}",
+                kind
+            ))))
+        }
+    };
+
+    Ok(Message { seq, command })
+}
+
+// 连接的认证状态，保留 auth 消息中的凭据，凭据变更后用于重新校验
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub authorization: Option<String>,
+    // 未认证时为 None
+    pub scope: Option<Scope>,
+}
+
+impl Session {
+    // 按保存的凭据重新确定权限，凭据被删除或降级后权限随之撤销
+    pub fn refresh(&mut self, authenticate: impl FnOnce(Option<&str>) -> Result<Scope, Denied>) {
+        self.scope = authenticate(self.authorization.as_deref()).ok();
+    }
+}
+
+// 连接建立时的权限，未启用认证时为 admin，否则需要先发送 auth 消息
+pub fn connect(device: &impl Device) -> Session {
+    let mut session = Session::default();
+    session.refresh(|authorization| device.authenticate(authorization));
+    session
+}
+
+// 执行一条客户端消息，返回要回复给该客户端的文本
+// session 为该连接当前的认证状态，auth 消息成功后更新
+pub fn handle(
+    device: &mut impl Device,
+    limits: &Limits,
+    session: &mut Session,
+    data: &[u8],
+) -> String {
+    let (seq, result) = match decode(data) {
+        Ok(message) => (
+            message.seq,
+            execute(device, limits, session, message.command),
+        ),
+        Err((seq, e)) => (seq, Err(e)),
+    };
+
+    match result {
+        Ok(ack) => serde_json::to_string(&Reply::Ack {
+            seq,
+            id: ack.id,
+            level: ack.level,
+            scope: ack.scope,
+        })
+        .unwrap_or_default(),
+        Err(e) => {
+            log::warn!("ws command error: {:?}", e);
+            encode_error(seq, &e)
+        }
+    }
+}
+
+// {"type": "error", ...}，也用于无法解码的消息，例如超长的帧
+pub fn encode_error(seq: Option<u64>, e: &ApiError) -> String {
+    serde_json::to_string(&Reply::Error {
+        seq,
+        code: e.code,
+        message: &e.message,
+    })
+    .unwrap_or_default()
+}
+
+// ack 中附带的结果
+#[derive(Debug, Default)]
+struct Ack {
+    id: Option<u32>,
+    level: Option<u8>,
+    scope: Option<Scope>,
+}
+
+fn execute(
+    device: &mut impl Device,
+    limits: &Limits,
+    session: &mut Session,
+    command: Command,
+) -> Result<Ack, ApiError> {
+    match command {
+        Command::Speak(request) => {
+            require(session.scope, Scope::Speak)?;
+            Ok(Ack {
+                id: Some(api::speak(device, limits, request)?),
+                ..Default::default()
+            })
+        }
+        Command::Stop => {
+            require(session.scope, Scope::Speak)?;
+            device.stop();
+            Ok(Ack::default())
+        }
+        Command::Volume(request) => {
+            require(session.scope, Scope::Speak)?;
+            Ok(Ack {
+                level: Some(api::set_volume(device, &request)?),
+                ..Default::default()
+            })
+        }
+        Command::Auth(request) => {
+            // 认证失败时撤销之前的权限
+            *session = Session::default();
+            let granted = device.authenticate(Some(&request.authorization))?;
+            *session = Session {
+                authorization: Some(request.authorization),
+                scope: Some(granted),
+            };
+            Ok(Ack {
+                scope: Some(granted),
+                ..Default::default()
+            })
+        }
+    }
+}
+
+// 未认证时为 401，权限不足时为 403
+pub fn require(scope: Option<Scope>, required: Scope) -> Result<(), ApiError> {
+    match scope {
+        Some(scope) if scope.allows(required) => Ok(()),
+        Some(_) => Err(Denied::Forbidden.into()),
+        None => Err(Denied::Missing.into()),
+    }
+}
+
+pub fn encode_event(event: &Event) -> String {
+    serde_json::to_string(event).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::api::fake::{FakeDevice, LIMITS};
+    use crate::auth::{Credential, Kind};
+
+    fn send(device: &mut FakeDevice, session: &mut Session, message: &str) -> Value {
+        serde_json::from_str(&handle(device, &LIMITS, session, message.as_bytes())).unwrap()
+    }
+
+    #[test]
+    fn decode_commands() {
+        let message =
+            decode(br#"{"type": "speak", "text": "hi", "pre": "tone:beep", "seq": 1}"#).unwrap();
+        assert_eq!(message.seq, Some(1));
+        let Command::Speak(request) = message.command else {
+            panic!("not speak");
+        };
+        assert_eq!(request.text, "hi");
+        assert_eq!(request.pre.as_deref(), Some("tone:beep"));
+
+        let message = decode(br#"{"type": "stop"}"#).unwrap();
+        assert!(message.seq.is_none());
+        assert!(matches!(message.command, Command::Stop));
+
+        let message = decode(br#"{"type": "volume", "op": "inc", "seq": null}"#).unwrap();
+        let Command::Volume(request) = message.command else {
+            panic!("not volume");
+        };
+        assert_eq!(
+            (request.level, request.op),
+            (None, Some(api::VolumeOp::Inc))
+        );
+
+        let message = decode(br#"{"type": "auth", "authorization": "Bearer x"}"#).unwrap();
+        let Command::Auth(request) = message.command else {
+            panic!("not auth");
+        };
+        assert_eq!(request.authorization, "Bearer x");
+    }
+
+    #[test]
+    fn decode_errors() {
+        let cases: &[(&str, Option<u64>, &str)] = &[
+            ("nope", None, "invalid_json"),
+            ("[1, 2]", None, "invalid_json"),
+            (r#"{"type": "stop", "seq": "a"}"#, None, "bad_request"),
+            (r#"{"seq": 2}"#, Some(2), "bad_request"),
+            (r#"{"type": 1, "seq": 3}"#, Some(3), "bad_request"),
+            (r#"{"type": "dance", "seq": 4}"#, Some(4), "bad_request"),
+            (
+                r#"{"type": "speak", "txt": "a", "seq": 5}"#,
+                Some(5),
+                "invalid_json",
+            ),
+            (
+                r#"{"type": "volume", "level": 300, "seq": 6}"#,
+                Some(6),
+                "invalid_json",
+            ),
+            (r#"{"type": "auth", "seq": 7}"#, Some(7), "invalid_json"),
+        ];
+        for (message, seq, code) in cases {
+            let (error_seq, e) = decode(message.as_bytes()).unwrap_err();
+            assert_eq!((error_seq, e.code), (*seq, *code), "{}", message);
+        }
+    }
+
+    #[test]
+    fn replies() {
+        let mut device = FakeDevice::default();
+        let mut session = connect(&device);
+        assert_eq!(session.scope, Some(Scope::Admin));
+
+        let reply = send(
+            &mut device,
+            &mut session,
+            r#"{"type": "speak", "text": "hi", "seq": 1}"#,
+        );
+        assert_eq!(reply, json!({"type": "ack", "seq": 1, "id": 1}));
+        let reply = send(
+            &mut device,
+            &mut session,
+            r#"{"type": "volume", "level": 9}"#,
+        );
+        assert_eq!(reply, json!({"type": "ack", "level": 9}));
+        let reply = send(&mut device, &mut session, r#"{"type": "stop", "seq": 2}"#);
+        assert_eq!(reply, json!({"type": "ack", "seq": 2}));
+        assert_eq!((device.volume, device.stopped), (9, 1));
+
+        let reply = send(
+            &mut device,
+            &mut session,
+            r#"{"type": "speak", "text": " ", "seq": 3}"#,
+        );
+        assert_eq!(reply["type"], "error");
+        assert_eq!(reply["seq"], 3);
+        assert_eq!(reply["code"], "bad_request");
+        let reply = send(&mut device, &mut session, "{");
+        assert_eq!(reply["code"], "invalid_json");
+        assert!(reply.get("seq").is_none());
+    }
+
+    #[test]
+    fn auth_messages() {
+        let mut device = FakeDevice::default();
+        let admin = Credential::new("admin", Kind::Token, Scope::Admin, "a", b"s", 1);
+        device.store.add(admin, 4).unwrap();
+        let speak = Credential::new("tv", Kind::Token, Scope::Speak, "t", b"s", 1);
+        device.store.add(speak, 4).unwrap();
+
+        let mut session = connect(&device);
+        assert_eq!(session.scope, None);
+        let reply = send(&mut device, &mut session, r#"{"type": "stop", "seq": 1}"#);
+        assert_eq!(reply["code"], "unauthorized");
+
+        let reply = send(
+            &mut device,
+            &mut session,
+            r#"{"type": "auth", "authorization": "Bearer t"}"#,
+        );
+        assert_eq!(reply, json!({"type": "ack", "scope": "speak"}));
+        assert_eq!(session.scope, Some(Scope::Speak));
+        assert_eq!(session.authorization.as_deref(), Some("Bearer t"));
+        let reply = send(&mut device, &mut session, r#"{"type": "stop"}"#);
+        assert_eq!(reply["type"], "ack");
+
+        // 认证失败后撤销之前的权限
+        let reply = send(
+            &mut device,
+            &mut session,
+            r#"{"type": "auth", "authorization": "Bearer x"}"#,
+        );
+        assert_eq!(reply["code"], "unauthorized");
+        assert_eq!(session.scope, None);
+        assert!(session.authorization.is_none());
+        assert_eq!(device.stopped, 1);
+    }
+
+    #[test]
+    fn credential_removed() {
+        let mut device = FakeDevice::default();
+        let admin = Credential::new("admin", Kind::Token, Scope::Admin, "a", b"s", 1);
+        device.store.add(admin, 4).unwrap();
+        let speak = Credential::new("tv", Kind::Token, Scope::Speak, "t", b"s", 1);
+        device.store.add(speak, 4).unwrap();
+
+        let mut session = connect(&device);
+        send(
+            &mut device,
+            &mut session,
+            r#"{"type": "auth", "authorization": "Bearer t"}"#,
+        );
+        session.refresh(|authorization| device.store.authenticate(authorization));
+        assert_eq!(session.scope, Some(Scope::Speak));
+
+        // 删除凭据后重新校验，之后的命令被拒绝
+        device.store.remove("tv").unwrap();
+        session.refresh(|authorization| device.store.authenticate(authorization));
+        assert_eq!(session.scope, None);
+        let reply = send(&mut device, &mut session, r#"{"type": "stop"}"#);
+        assert_eq!(reply["code"], "unauthorized");
+        assert_eq!(device.stopped, 0);
+
+        // 删除全部凭据后认证关闭
+        device.store.remove("admin").unwrap();
+        session.refresh(|authorization| device.store.authenticate(authorization));
+        assert_eq!(session.scope, Some(Scope::Admin));
+    }
+
+    #[test]
+    fn encode() {
+        let cases = [
+            (Event::Queued { id: 1 }, json!({"type": "queued", "id": 1})),
+            (
+                Event::Started { id: 2 },
+                json!({"type": "started", "id": 2}),
+            ),
+            (
+                Event::Finished { id: 3 },
+                json!({"type": "finished", "id": 3}),
+            ),
+            (
+                Event::Stopped { id: 4 },
+                json!({"type": "stopped", "id": 4}),
+            ),
+            (Event::Wake { word: 0 }, json!({"type": "wake", "word": 0})),
+            (
+                Event::Command {
+                    phrase: "da kai".to_string(),
+                },
+                json!({"type": "command", "phrase": "da kai"}),
+            ),
+            (
+                Event::Volume { level: 5 },
+                json!({"type": "volume", "level": 5}),
+            ),
+            (
+                Event::Button { button: "k0" },
+                json!({"type": "button", "button": "k0"}),
+            ),
+            (
+                Event::Wifi {
+                    change: "client_connected",
+                    clients: 1,
+                },
+                json!({"type": "wifi", "change": "client_connected", "clients": 1}),
+            ),
+        ];
+        for (event, expected) in cases {
+            let encoded: Value = serde_json::from_str(&encode_event(&event)).unwrap();
+            assert_eq!(encoded, expected);
+        }
+
+        let error = encode_error(Some(7), &ApiError::payload_too_large(2048));
+        let error: Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(
+            error,
+            json!({
+                "type": "error",
+                "seq": 7,
+                "code": "payload_too_large",
+                "message": "request body exceeds 2048 bytes",
+            })
+        );
+    }
+}
@@ -0,0 +1,205 @@
+// 播报队列：按优先级排序，同优先级先进先出，可查看、取消和清空
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+#[derive(Debug)]
+pub struct Entry<T> {
+    pub id: u32,
+    // 数值越大越先播放
+    pub priority: u8,
+    pub enqueued: Instant,
+    pub item: T,
+}
+
+// 队列已满
+#[derive(Debug)]
+pub struct Full {
+    pub max_len: usize,
+}
+
+impl fmt::Display for Full {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "queue full ({} pending)", self.max_len)
+    }
+}
+
+impl std::error::Error for Full {}
+
+pub struct Queue<T> {
+    entries: Mutex<VecDeque<Entry<T>>>,
+    ready: Condvar,
+    max_len: AtomicUsize,
+    // 下一个任务 ID，只在持有 entries 锁时分配
+    next_id: AtomicU32,
+}
+
+impl<T> Queue<T> {
+    pub const fn new(max_len: usize) -> Self {
+        Queue {
+            entries: Mutex::new(VecDeque::new()),
+            ready: Condvar::new(),
+            max_len: AtomicUsize::new(max_len),
+            next_id: AtomicU32::new(1),
+        }
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len.load(Ordering::Relaxed)
+    }
+
+    // 只限制之后的 push，已在队列中的不受影响
+    pub fn set_max_len(&self, max_len: usize) {
+        self.max_len.store(max_len, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    // 插入到同优先级的最后，返回分配的 ID
+    pub fn push(&self, priority: u8, item: T) -> Result<u32, Full> {
+        let mut entries = self.entries.lock().unwrap();
+        let max_len = self.max_len();
+        if entries.len() >= max_len {
+            return Err(Full { max_len });
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        let pos = entries
+            .iter()
+            .position(|e| e.priority < priority)
+            .unwrap_or(entries.len());
+        entries.insert(
+            pos,
+            Entry {
+                id,
+                priority,
+                enqueued: Instant::now(),
+                item,
+            },
+        );
+        self.ready.notify_one();
+        Ok(id)
+    }
+
+    // 阻塞直到有任务
+    pub fn pop(&self) -> Entry<T> {
+        let mut entries = self.entries.lock().unwrap();
+        loop {
+            if let Some(entry) = entries.pop_front() {
+                return entry;
+            }
+            entries = self.ready.wait(entries).unwrap();
+        }
+    }
+
+    pub fn remove(&self, id: u32) -> Option<Entry<T>> {
+        let mut entries = self.entries.lock().unwrap();
+        let pos = entries.iter().position(|e| e.id == id)?;
+        entries.remove(pos)
+    }
+
+    // 清空并返回被移除的任务
+    pub fn clear(&self) -> Vec<Entry<T>> {
+        self.entries.lock().unwrap().drain(..).collect()
+    }
+
+    // 按播放顺序列出队列中的任务
+    pub fn list<R>(&self, f: impl Fn(&Entry<T>) -> R) -> Vec<R> {
+        self.entries.lock().unwrap().iter().map(f).collect()
+    }
+
+    // 队列中的任务及下一个 ID，在同一次加锁中读取
+    // ID 小于返回值且不在列表中的任务均已被取出或移除
+    pub fn snapshot<R>(&self, f: impl Fn(&Entry<T>) -> R) -> (Vec<R>, u32) {
+        let entries = self.entries.lock().unwrap();
+        let next_id = self.next_id.load(Ordering::Relaxed);
+        (entries.iter().map(f).collect(), next_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    fn ids(queue: &Queue<()>) -> Vec<u32> {
+        queue.list(|e| e.id)
+    }
+
+    #[test]
+    fn priority_then_fifo() {
+        let queue = Queue::new(8);
+        for priority in [0, 5, 0, 5, 9] {
+            queue.push(priority, ()).unwrap();
+        }
+        assert_eq!(ids(&queue), [5, 2, 4, 1, 3]);
+        assert_eq!(queue.pop().id, 5);
+        assert_eq!(queue.count(), 4);
+    }
+
+    #[test]
+    fn full() {
+        let queue = Queue::new(2);
+        assert_eq!(queue.push(0, ()).unwrap(), 1);
+        assert_eq!(queue.push(0, ()).unwrap(), 2);
+        let err = queue.push(9, ()).unwrap_err();
+        assert_eq!(err.max_len, 2);
+        assert_eq!(ids(&queue), [1, 2]);
+
+        // 缩小上限不移除已有的任务
+        queue.set_max_len(1);
+        assert_eq!(queue.count(), 2);
+        assert!(queue.push(0, ()).is_err());
+        queue.set_max_len(3);
+        // 失败的 push 不占用 ID
+        assert_eq!(queue.push(0, ()).unwrap(), 3);
+        assert_eq!(ids(&queue), [1, 2, 3]);
+    }
+
+    #[test]
+    fn remove_and_clear() {
+        let queue = Queue::new(8);
+        for _ in 1..=4 {
+            queue.push(0, ()).unwrap();
+        }
+        assert_eq!(queue.remove(2).map(|e| e.id), Some(2));
+        assert!(queue.remove(2).is_none());
+        assert_eq!(ids(&queue), [1, 3, 4]);
+
+        let removed: Vec<u32> = queue.clear().iter().map(|e| e.id).collect();
+        assert_eq!(removed, [1, 3, 4]);
+        assert_eq!(queue.count(), 0);
+        assert!(queue.clear().is_empty());
+    }
+
+    #[test]
+    fn pop_waits_for_push() {
+        let queue = Arc::new(Queue::new(8));
+        let consumer = {
+            let queue = queue.clone();
+            thread::spawn(move || queue.pop().item)
+        };
+        thread::sleep(Duration::from_millis(50));
+        assert!(!consumer.is_finished());
+        queue.push(0, "hello").unwrap();
+        assert_eq!(consumer.join().unwrap(), "hello");
+    }
+
+    #[test]
+    fn snapshot() {
+        let queue = Queue::new(8);
+        assert_eq!(queue.snapshot(|e| e.id), (vec![], 1));
+        for _ in 1..=3 {
+            queue.push(0, ()).unwrap();
+        }
+        queue.pop();
+        assert_eq!(queue.snapshot(|e| e.id), (vec![2, 3], 4));
+    }
+}
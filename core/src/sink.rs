@@ -0,0 +1,227 @@
+// 音频输出
+// AudioSink 由音频线程调用，写入单声道 16 位 PCM (global::SAMPLE_RATE)
+// 本文件中的实现不依赖 esp-idf，可在主机上运行音频管线
+
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::wav;
+
+pub trait AudioSink: Send {
+    fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()>;
+}
+
+// 写入超时，例如 I2S DMA 缓冲区长时间没有空间
+#[derive(Debug)]
+pub struct Timeout;
+
+impl std::fmt::Display for Timeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "audio sink write timeout")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+// 丢弃所有数据，仅统计写入的采样数
+#[derive(Debug, Default)]
+pub struct NullSink {
+    pub written: usize,
+}
+
+impl AudioSink for NullSink {
+    fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+        self.written += pcm.len();
+        Ok(())
+    }
+}
+
+// 写入 WAV 文件，finish 时更新文件头中的长度
+pub struct WavSink<W: Write + Seek + Send> {
+    writer: W,
+    data_len: u32,
+    sample_rate: u32,
+}
+
+impl<W: Write + Seek + Send> WavSink<W> {
+    pub fn new(mut writer: W, sample_rate: u32) -> anyhow::Result<Self> {
+        writer.write_all(&wav::header(sample_rate, 1, 0))?;
+        Ok(WavSink {
+            writer,
+            data_len: 0,
+            sample_rate,
+        })
+    }
+
+    // 更新文件头并返回 writer
+    pub fn finish(mut self) -> anyhow::Result<W> {
+        self.update_header()?;
+        Ok(self.writer)
+    }
+
+    fn update_header(&mut self) -> anyhow::Result<()> {
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(&wav::header(self.sample_rate, 1, self.data_len))?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek + Send> AudioSink for WavSink<W> {
+    fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+        let mut buf = Vec::with_capacity(pcm.len() * 2);
+        for sample in pcm {
+            buf.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.writer.write_all(&buf)?;
+        self.data_len += buf.len() as u32;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::{mpsc, Arc, Mutex};
+
+    use super::*;
+    use crate::audio::{self, Audio, Packet};
+    use crate::event::{self, Event};
+    use crate::global;
+
+    const FRAME: usize = global::MIXER_FRAME_SAMPLES;
+
+    // 音频线程使用全局混音器，测试之间不能并行
+    static LOCK: Mutex<()> = Mutex::new(());
+
+    // 音频线程拥有 sink，测试结束后通过共享的引用取回
+    struct Shared<S>(Arc<Mutex<S>>);
+
+    impl<S: AudioSink> AudioSink for Shared<S> {
+        fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+            self.0.lock().unwrap().write(pcm)
+        }
+    }
+
+    // ID 为 STOPPED 的播报视为已被停止
+    const STOPPED: u32 = 103;
+
+    fn is_stopped(id: u32) -> bool {
+        id == STOPPED
+    }
+
+    // 数据包经音频线程的队列送入 Audio::play_with_tx，发送端关闭后返回
+    fn run<S: AudioSink + 'static>(packets: Vec<Packet>, sink: S) -> S {
+        global::init();
+        let (tx, rx) = mpsc::sync_channel(packets.len());
+        for packet in packets {
+            audio::send(&tx, packet);
+        }
+        drop(tx);
+
+        let sink = Arc::new(Mutex::new(sink));
+        Audio::new(Box::new(Shared(sink.clone())), is_stopped).play_with_tx(rx);
+        Arc::into_inner(sink).unwrap().into_inner().unwrap()
+    }
+
+    fn pcm(p: usize) -> Vec<i16> {
+        (0..300).map(|i| (p * 1000 + i) as i16 - 2500).collect()
+    }
+
+    fn speech(id: u32, data: &[Vec<i16>]) -> Vec<Packet> {
+        let mut packets = vec![Packet::Begin(id)];
+        packets.extend(data.iter().map(|pcm| Packet::Data(id, pcm.clone())));
+        packets.push(Packet::End(id));
+        packets
+    }
+
+    fn decode(sink: WavSink<Cursor<Vec<u8>>>) -> Vec<i16> {
+        let buf = sink.finish().unwrap().into_inner();
+        let header = wav::parse_header(&buf).unwrap();
+        assert_eq!(header.format.sample_rate, global::SAMPLE_RATE);
+        assert_eq!(header.format.channels, 1);
+        assert_eq!(header.data_offset + header.data_len, buf.len());
+        wav::decode(&header.format, &buf[header.data_offset..])
+    }
+
+    fn wav_sink() -> WavSink<Cursor<Vec<u8>>> {
+        WavSink::new(Cursor::new(Vec::new()), global::SAMPLE_RATE).unwrap()
+    }
+
+    #[test]
+    fn pipeline_into_wav() {
+        let _lock = LOCK.lock().unwrap();
+        let data: Vec<Vec<i16>> = (0..5).map(pcm).collect();
+        let written = decode(run(speech(101, &data), wav_sink()));
+
+        // 最后不足一帧的部分补零
+        let expected = data.concat();
+        assert_eq!(written.len(), expected.len().div_ceil(FRAME) * FRAME);
+        assert_eq!(&written[..expected.len()], &expected[..]);
+        assert!(written[expected.len()..].iter().all(|s| *s == 0));
+    }
+
+    #[test]
+    fn pipeline_into_null() {
+        let _lock = LOCK.lock().unwrap();
+        let data: Vec<Vec<i16>> = (0..5).map(pcm).collect();
+        let sink = run(speech(101, &data), NullSink::default());
+        assert_eq!(sink.written, 1536);
+    }
+
+    #[test]
+    fn gap_and_stop() {
+        let _lock = LOCK.lock().unwrap();
+        let events = event::subscribe();
+        let frames = audio::stats().frames;
+
+        let mut packets = speech(101, &[pcm(0)]);
+        packets.extend(speech(102, &[pcm(1)]));
+        packets.extend(speech(STOPPED, &[pcm(2)]));
+        let written = decode(run(packets, wav_sink()));
+
+        // 两次播报之间插入 SPEECH_GAP_MS 的静音，已停止的播报不输出
+        let gap = (global::SAMPLE_RATE * global::SPEECH_GAP_MS / 1000) as usize;
+        let mut expected = pcm(0);
+        expected.extend(vec![0; gap]);
+        expected.extend(pcm(1));
+        assert_eq!(written.len(), expected.len().div_ceil(FRAME) * FRAME);
+        assert_eq!(&written[..expected.len()], &expected[..]);
+        assert!(written[expected.len()..].iter().all(|s| *s == 0));
+        assert_eq!(
+            audio::stats().frames - frames,
+            (written.len() / FRAME) as u64
+        );
+
+        let lifecycle: Vec<String> = events
+            .try_iter()
+            .filter(|e| {
+                matches!(
+                    e,
+                    Event::Started { .. } | Event::Finished { .. } | Event::Stopped { .. }
+                )
+            })
+            .map(|e| format!("{:?}", e))
+            .collect();
+        assert_eq!(
+            lifecycle,
+            [
+                "Started { id: 101 }",
+                "Finished { id: 101 }",
+                "Started { id: 102 }",
+                "Finished { id: 102 }",
+                "Stopped { id: 103 }",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_wav() {
+        let sink = WavSink::new(Cursor::new(Vec::new()), 8000).unwrap();
+        let buf = sink.finish().unwrap().into_inner();
+        let header = wav::parse_header(&buf).unwrap();
+        assert_eq!(header.data_len, 0);
+        assert_eq!(buf.len(), 44);
+    }
+}
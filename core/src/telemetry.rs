@@ -0,0 +1,92 @@
+// 音频线程的播放统计：输出电平、削波、I2S 写入超时、断流与队列深度
+
+use serde::Serialize;
+
+// VU 表每帧的衰减量 (dB)，16ms 一帧约 20dB/s
+const VU_DECAY_DB: f32 = 0.3;
+// 电平下限 (dBFS)
+const FLOOR_DB: f32 = -90.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Telemetry {
+    // 最近一帧的峰值与均方根电平 (dBFS)
+    pub peak_db: f32,
+    pub rms_db: f32,
+    // 峰值保持并缓慢衰减的电平，用于 VU 表
+    pub vu_db: f32,
+    // 已输出的帧数与采样数
+    pub frames: u64,
+    pub samples: u64,
+    // 主音量增益后超出 16 位范围、被限幅器压低的采样数
+    pub clipped: u64,
+    // sink 写入超时 / 其他错误次数
+    pub write_timeouts: u64,
+    pub write_errors: u64,
+    // 播报进行中语音数据没有及时送达的次数
+    pub underruns: u64,
+    // 音频队列中等待处理的数据包数量
+    pub queue_depth: usize,
+    pub max_queue_depth: usize,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Telemetry {
+            peak_db: FLOOR_DB,
+            rms_db: FLOOR_DB,
+            vu_db: FLOOR_DB,
+            frames: 0,
+            samples: 0,
+            clipped: 0,
+            write_timeouts: 0,
+            write_errors: 0,
+            underruns: 0,
+            queue_depth: 0,
+            max_queue_depth: 0,
+        }
+    }
+}
+
+impl Telemetry {
+    // 记录一帧输出
+    pub fn record_frame(&mut self, frame: &[i16], clipped: usize) {
+        if frame.is_empty() {
+            return;
+        }
+
+        let peak = frame.iter().map(|s| (*s as i32).abs()).max().unwrap_or(0);
+        let sum: f64 = frame.iter().map(|s| (*s as f64) * (*s as f64)).sum();
+        let rms = (sum / frame.len() as f64).sqrt();
+
+        self.peak_db = to_db(peak as f32);
+        self.rms_db = to_db(rms as f32);
+        self.vu_db = (self.vu_db - VU_DECAY_DB).max(self.peak_db);
+        self.frames += 1;
+        self.samples += frame.len() as u64;
+        self.clipped += clipped as u64;
+    }
+
+    // 没有输出时电平归零
+    pub fn record_idle(&mut self) {
+        self.peak_db = FLOOR_DB;
+        self.rms_db = FLOOR_DB;
+        self.vu_db = FLOOR_DB;
+    }
+
+    pub fn record_queue_depth(&mut self, depth: usize) {
+        self.queue_depth = depth;
+        self.max_queue_depth = self.max_queue_depth.max(depth);
+    }
+
+    // VU 表刻度 0 ~ 100，对应 -60 ~ 0 dBFS
+    pub fn vu_percent(&self) -> u8 {
+        ((self.vu_db + 60.0) / 60.0 * 100.0).clamp(0.0, 100.0) as u8
+    }
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return FLOOR_DB;
+    }
+    (20.0 * (amplitude / 32768.0).log10()).max(FLOOR_DB)
+}
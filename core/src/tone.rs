@@ -0,0 +1,302 @@
+// 音调生成：正弦 / 方波、扫频、多段序列与 DTMF 按键音
+
+use std::f32::consts::PI;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+// DTMF 按键的行 / 列频率
+const DTMF_ROWS: [u32; 4] = [697, 770, 852, 941];
+const DTMF_COLS: [u32; 4] = [1209, 1336, 1477, 1633];
+const DTMF_KEYS: [&str; 4] = ["123A", "456B", "789C", "*0#D"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Square,
+}
+
+// 每段音调的淡入淡出时长，避免爆音
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Envelope {
+    pub attack_ms: u32,
+    pub release_ms: u32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Envelope {
+            attack_ms: 10,
+            release_ms: 10,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Tone {
+    // 单频，或多个频率叠加
+    #[serde(rename = "tone")]
+    Note {
+        freqs: Vec<u32>,
+        ms: u32,
+        #[serde(default)]
+        waveform: Waveform,
+    },
+    // 从 from 线性扫频到 to
+    Sweep {
+        from: u32,
+        to: u32,
+        ms: u32,
+        #[serde(default)]
+        waveform: Waveform,
+    },
+    Silence {
+        ms: u32,
+    },
+    // 按键音，每个按键 ms 毫秒，按键间隔 gap_ms 毫秒
+    Dtmf {
+        digits: String,
+        #[serde(default = "default_dtmf_ms")]
+        ms: u32,
+        #[serde(default = "default_dtmf_ms")]
+        gap_ms: u32,
+    },
+}
+
+fn default_dtmf_ms() -> u32 {
+    100
+}
+
+fn default_volume() -> f32 {
+    0.5
+}
+
+// 依次播放的音调序列
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    pub tones: Vec<Tone>,
+    #[serde(default)]
+    pub envelope: Envelope,
+    // 0.0 ~ 1.0
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+}
+
+impl Sequence {
+    pub fn new(tones: Vec<Tone>) -> Self {
+        Sequence {
+            tones,
+            envelope: Envelope::default(),
+            volume: default_volume(),
+        }
+    }
+
+    // 序列总时长
+    pub fn duration_ms(&self) -> u64 {
+        self.tones
+            .iter()
+            .map(|tone| match tone {
+                Tone::Note { ms, .. } | Tone::Sweep { ms, .. } | Tone::Silence { ms } => *ms as u64,
+                Tone::Dtmf { digits, ms, gap_ms } => {
+                    digits.chars().count() as u64 * (*ms as u64 + *gap_ms as u64)
+                }
+            })
+            .sum()
+    }
+
+    pub fn validate(&self, sample_rate: u32, max_ms: u64) -> anyhow::Result<()> {
+        if self.tones.is_empty() {
+            bail!("empty tone sequence");
+        }
+        if !(0.0..=1.0).contains(&self.volume) {
+            bail!("volume out of range: {}", self.volume);
+        }
+        if self.duration_ms() > max_ms {
+            bail!("tone sequence longer than {}ms", max_ms);
+        }
+
+        let valid = |freq: u32| (20..sample_rate / 2).contains(&freq);
+        for tone in &self.tones {
+            match tone {
+                Tone::Note { freqs, .. } => {
+                    if freqs.is_empty() || !freqs.iter().all(|f| valid(*f)) {
+                        bail!("invalid frequencies: {:?}", freqs);
+                    }
+                }
+                Tone::Sweep { from, to, .. } => {
+                    if !valid(*from) || !valid(*to) {
+                        bail!("invalid sweep: {} -> {}", from, to);
+                    }
+                }
+                Tone::Silence { .. } => {}
+                Tone::Dtmf { digits, .. } => {
+                    if let Some(c) = digits.chars().find(|c| dtmf_freqs(*c).is_none()) {
+                        bail!("invalid dtmf digit: {:?}", c);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 生成 PCM，调用前需先 validate
+    pub fn render(&self, sample_rate: u32) -> Vec<i16> {
+        let amplitude = self.volume * i16::MAX as f32;
+        let mut pcm = Vec::with_capacity((self.duration_ms() * sample_rate as u64 / 1000) as usize);
+        for tone in &self.tones {
+            match tone {
+                Tone::Note {
+                    freqs,
+                    ms,
+                    waveform,
+                } => {
+                    let freqs: Vec<f32> = freqs.iter().map(|f| *f as f32).collect();
+                    let start = pcm.len();
+                    synth(
+                        &mut pcm,
+                        &freqs,
+                        &freqs,
+                        *ms,
+                        *waveform,
+                        sample_rate,
+                        amplitude,
+                    );
+                    apply_envelope(&mut pcm[start..], &self.envelope, sample_rate);
+                }
+                Tone::Sweep {
+                    from,
+                    to,
+                    ms,
+                    waveform,
+                } => {
+                    let start = pcm.len();
+                    let (from, to) = ([*from as f32], [*to as f32]);
+                    synth(&mut pcm, &from, &to, *ms, *waveform, sample_rate, amplitude);
+                    apply_envelope(&mut pcm[start..], &self.envelope, sample_rate);
+                }
+                Tone::Silence { ms } => {
+                    pcm.resize(pcm.len() + samples(*ms, sample_rate), 0);
+                }
+                Tone::Dtmf { digits, ms, gap_ms } => {
+                    for (row, col) in digits.chars().filter_map(dtmf_freqs) {
+                        let freqs = [row as f32, col as f32];
+                        let start = pcm.len();
+                        synth(
+                            &mut pcm,
+                            &freqs,
+                            &freqs,
+                            *ms,
+                            Waveform::Sine,
+                            sample_rate,
+                            amplitude,
+                        );
+                        apply_envelope(&mut pcm[start..], &self.envelope, sample_rate);
+                        pcm.resize(pcm.len() + samples(*gap_ms, sample_rate), 0);
+                    }
+                }
+            }
+        }
+        pcm
+    }
+}
+
+// 内置音调，可用作播报前后的提示音
+pub fn preset(name: &str) -> Option<Sequence> {
+    let note = |freq: u32, ms: u32| Tone::Note {
+        freqs: vec![freq],
+        ms,
+        waveform: Waveform::Sine,
+    };
+    let tones = match name {
+        "beep" => vec![note(1000, 150)],
+        // 叮咚
+        "ding_dong" => vec![note(784, 300), note(659, 500)],
+        "alert" => vec![
+            note(880, 120),
+            Tone::Silence { ms: 60 },
+            note(880, 120),
+            Tone::Silence { ms: 60 },
+            note(880, 120),
+        ],
+        "rise" => vec![Tone::Sweep {
+            from: 400,
+            to: 1200,
+            ms: 300,
+            waveform: Waveform::Sine,
+        }],
+        _ => return None,
+    };
+    Some(Sequence::new(tones))
+}
+
+// 单频正弦波，带默认淡入淡出
+pub fn sine(freq: u32, ms: u32, sample_rate: u32, volume: f32) -> Vec<i16> {
+    let mut sequence = Sequence::new(vec![Tone::Note {
+        freqs: vec![freq],
+        ms,
+        waveform: Waveform::Sine,
+    }]);
+    sequence.volume = volume;
+    sequence.render(sample_rate)
+}
+
+pub fn dtmf_freqs(digit: char) -> Option<(u32, u32)> {
+    let digit = digit.to_ascii_uppercase();
+    DTMF_KEYS
+        .iter()
+        .enumerate()
+        .find_map(|(row, keys)| keys.find(digit).map(|col| (DTMF_ROWS[row], DTMF_COLS[col])))
+}
+
+fn samples(ms: u32, sample_rate: u32) -> usize {
+    (sample_rate as u64 * ms as u64 / 1000) as usize
+}
+
+// 频率从 from 线性变化到 to，多个频率等幅叠加
+fn synth(
+    pcm: &mut Vec<i16>,
+    from: &[f32],
+    to: &[f32],
+    ms: u32,
+    waveform: Waveform,
+    sample_rate: u32,
+    amplitude: f32,
+) {
+    let len = samples(ms, sample_rate);
+    let mut phases = vec![0f32; from.len()];
+    let scale = amplitude / from.len() as f32;
+    for i in 0..len {
+        let t = i as f32 / len as f32;
+        let mut value = 0.0;
+        for (k, phase) in phases.iter_mut().enumerate() {
+            let freq = from[k] + (to[k] - from[k]) * t;
+            *phase = (*phase + 2.0 * PI * freq / sample_rate as f32) % (2.0 * PI);
+            value += match waveform {
+                Waveform::Sine => phase.sin(),
+                Waveform::Square => {
+                    if *phase < PI {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+            };
+        }
+        pcm.push((value * scale) as i16);
+    }
+}
+
+fn apply_envelope(pcm: &mut [i16], envelope: &Envelope, sample_rate: u32) {
+    let len = pcm.len();
+    let attack = samples(envelope.attack_ms, sample_rate).min(len / 2);
+    let release = samples(envelope.release_ms, sample_rate).min(len / 2);
+    for (i, sample) in pcm.iter_mut().enumerate().take(attack) {
+        *sample = (*sample as f32 * i as f32 / attack as f32) as i16;
+    }
+    for (i, sample) in pcm.iter_mut().rev().enumerate().take(release) {
+        *sample = (*sample as f32 * i as f32 / release as f32) as i16;
+    }
+}
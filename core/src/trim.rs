@@ -0,0 +1,122 @@
+// 首尾静音裁剪：按 10ms 块计算能量，裁掉合成语音开头和结尾的静音
+// 中间的停顿原样保留；尾部静音只有在后面出现声音时才输出，不增加起播延迟
+
+use serde::{Deserialize, Serialize};
+
+const BLOCK_MS: u32 = 10;
+// 声音前后保留的静音 (ms)，避免切掉弱起的辅音和尾音
+const LEAD_PAD_MS: u32 = 20;
+const TAIL_PAD_MS: u32 = 50;
+// 最多暂存的静音 (ms)，更长的停顿超出部分直接输出
+const MAX_HOLD_MS: u32 = 2000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Settings {
+    pub enabled: bool,
+    // 低于该电平的块视为静音 (dBFS)
+    pub threshold_db: f32,
+    // 相邻两次播报之间固定的间隔 (ms)
+    pub gap_ms: u32,
+}
+
+impl Settings {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !(-80.0..=-20.0).contains(&self.threshold_db) || self.gap_ms > 5000 {
+            anyhow::bail!("invalid silence settings: {:?}", self);
+        }
+        Ok(())
+    }
+}
+
+pub struct Trimmer {
+    enabled: bool,
+    // 均方值阈值
+    threshold: f64,
+    block: usize,
+    lead_pad: usize,
+    tail_pad: usize,
+    max_hold: usize,
+    // 已经出现过声音
+    started: bool,
+    // 不足一块的输入
+    pending: Vec<i16>,
+    // 暂存的静音
+    held: Vec<i16>,
+}
+
+impl Trimmer {
+    pub fn new(settings: Settings, sample_rate: u32) -> Self {
+        let samples = |ms: u32| (sample_rate * ms / 1000) as usize;
+        Trimmer {
+            enabled: settings.enabled,
+            threshold: 10f64.powf(settings.threshold_db as f64 / 10.0) * 32768.0 * 32768.0,
+            block: samples(BLOCK_MS),
+            lead_pad: samples(LEAD_PAD_MS),
+            tail_pad: samples(TAIL_PAD_MS),
+            max_hold: samples(MAX_HOLD_MS),
+            started: false,
+            pending: Vec::new(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn process(&mut self, pcm: &[i16]) -> Vec<i16> {
+        if !self.enabled {
+            return pcm.to_vec();
+        }
+
+        self.pending.extend_from_slice(pcm);
+        let mut out = Vec::with_capacity(self.pending.len());
+        let blocks = self.pending.len() / self.block;
+        let pending: Vec<i16> = self.pending.drain(..blocks * self.block).collect();
+        for block in pending.chunks_exact(self.block) {
+            let sum_sq: f64 = block.iter().map(|s| (*s as f64) * (*s as f64)).sum();
+            let voiced = sum_sq / block.len() as f64 > self.threshold;
+
+            if voiced {
+                // 开头只保留声音前的一小段静音
+                if !self.started {
+                    let skip = self.held.len().saturating_sub(self.lead_pad);
+                    self.held.drain(..skip);
+                    self.started = true;
+                }
+                out.append(&mut self.held);
+                out.extend_from_slice(block);
+            } else {
+                self.held.extend_from_slice(block);
+                let limit = if self.started {
+                    self.max_hold
+                } else {
+                    self.lead_pad
+                };
+                if self.held.len() > limit {
+                    let n = self.held.len() - limit;
+                    if self.started {
+                        out.extend(self.held.drain(..n));
+                    } else {
+                        self.held.drain(..n);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // 输入结束，尾部静音只保留 tail_pad
+    pub fn finish(&mut self) -> Vec<i16> {
+        if !self.enabled {
+            return Vec::new();
+        }
+
+        let mut out = Vec::new();
+        if self.started {
+            self.held.append(&mut self.pending);
+            self.held.truncate(self.tail_pad);
+            out.append(&mut self.held);
+        }
+        self.started = false;
+        self.pending.clear();
+        self.held.clear();
+        out
+    }
+}
@@ -0,0 +1,467 @@
+// WAV / PCM 解析与重采样
+
+use anyhow::bail;
+
+use crate::adpcm;
+
+// PCM 格式描述
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+}
+
+impl Format {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.channels == 0 || self.channels > 2 {
+            bail!("unsupported channels: {}", self.channels);
+        }
+        if self.sample_rate < 4000 || self.sample_rate > 48000 {
+            bail!("unsupported sample rate: {}", self.sample_rate);
+        }
+        if self.bits_per_sample != 8 && self.bits_per_sample != 16 {
+            bail!("unsupported bits per sample: {}", self.bits_per_sample);
+        }
+        Ok(())
+    }
+
+    // 每帧（所有声道一个采样点）的字节数
+    pub fn block_align(&self) -> usize {
+        self.channels as usize * (self.bits_per_sample as usize / 8)
+    }
+}
+
+// data chunk 的编码方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Codec {
+    Pcm,
+    ImaAdpcm {
+        block_align: usize,
+    },
+    MsAdpcm {
+        block_align: usize,
+        coefs: Vec<(i16, i16)>,
+    },
+}
+
+// WAV 文件头，data_offset 为 data chunk 在文件中的偏移
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub format: Format,
+    pub codec: Codec,
+    pub data_offset: usize,
+    pub data_len: usize,
+}
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_MS_ADPCM: u16 = 0x0002;
+const WAVE_FORMAT_IMA_ADPCM: u16 = 0x0011;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+pub fn is_wav(buf: &[u8]) -> bool {
+    buf.len() >= 12 && &buf[0..4] == b"RIFF" && &buf[8..12] == b"WAVE"
+}
+
+fn parse_fmt(body: &[u8]) -> anyhow::Result<(Format, Codec)> {
+    if body.len() < 16 {
+        bail!("fmt chunk too short");
+    }
+    let tag = u16::from_le_bytes([body[0], body[1]]);
+    let format = Format {
+        channels: u16::from_le_bytes([body[2], body[3]]),
+        sample_rate: u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+        bits_per_sample: u16::from_le_bytes([body[14], body[15]]),
+    };
+    let block_align = u16::from_le_bytes([body[12], body[13]]) as usize;
+
+    let codec = match tag {
+        WAVE_FORMAT_PCM | WAVE_FORMAT_EXTENSIBLE => {
+            format.validate()?;
+            Codec::Pcm
+        }
+        WAVE_FORMAT_IMA_ADPCM | WAVE_FORMAT_MS_ADPCM => {
+            // ADPCM 采样位数为 4，仅校验声道与采样率
+            Format {
+                bits_per_sample: 16,
+                ..format
+            }
+            .validate()?;
+            if format.bits_per_sample != 4 || block_align < 8 * format.channels as usize {
+                bail!(
+                    "invalid adpcm block: {} bits, {} bytes",
+                    format.bits_per_sample,
+                    block_align
+                );
+            }
+            if tag == WAVE_FORMAT_IMA_ADPCM {
+                Codec::ImaAdpcm { block_align }
+            } else {
+                Codec::MsAdpcm {
+                    block_align,
+                    coefs: parse_ms_coefs(body),
+                }
+            }
+        }
+        _ => bail!("unsupported wav format tag: 0x{:04X}", tag),
+    };
+
+    Ok((format, codec))
+}
+
+// fmt chunk 扩展部分：cbSize, samplesPerBlock, numCoef, coefs[]
+fn parse_ms_coefs(body: &[u8]) -> Vec<(i16, i16)> {
+    if body.len() < 22 {
+        return adpcm::MS_DEFAULT_COEFS.to_vec();
+    }
+    let count = u16::from_le_bytes([body[20], body[21]]) as usize;
+    let coefs: Vec<(i16, i16)> = body[22..]
+        .chunks_exact(4)
+        .take(count)
+        .map(|c| {
+            (
+                i16::from_le_bytes([c[0], c[1]]),
+                i16::from_le_bytes([c[2], c[3]]),
+            )
+        })
+        .collect();
+    if coefs.is_empty() {
+        adpcm::MS_DEFAULT_COEFS.to_vec()
+    } else {
+        coefs
+    }
+}
+
+// 解析 RIFF/WAVE 文件头，buf 只需包含 data chunk 之前的部分
+pub fn parse_header(buf: &[u8]) -> anyhow::Result<Header> {
+    if !is_wav(buf) {
+        bail!("not a RIFF/WAVE file");
+    }
+
+    let mut fmt = None;
+    let mut pos = 12;
+    while pos + 8 <= buf.len() {
+        let id = &buf[pos..pos + 4];
+        let size = u32::from_le_bytes([buf[pos + 4], buf[pos + 5], buf[pos + 6], buf[pos + 7]]);
+        let body_start = pos + 8;
+
+        match id {
+            b"fmt " => {
+                let body_end = body_start.saturating_add(size as usize).min(buf.len());
+                fmt = Some(parse_fmt(&buf[body_start..body_end])?);
+            }
+            b"data" => {
+                let Some((format, codec)) = fmt else {
+                    bail!("data chunk before fmt chunk");
+                };
+                return Ok(Header {
+                    format,
+                    codec,
+                    data_offset: body_start,
+                    data_len: size as usize,
+                });
+            }
+            _ => {}
+        }
+
+        // chunk 按 2 字节对齐，size 接近 u32::MAX 时在 32 位平台上会溢出
+        pos = match body_start
+            .checked_add(size as usize)
+            .and_then(|end| end.checked_add(size as usize & 1))
+        {
+            Some(pos) => pos,
+            None => bail!("chunk size out of range"),
+        };
+    }
+
+    bail!("missing data chunk")
+}
+
+// 生成 16 位 PCM WAV 文件头
+pub fn header(sample_rate: u32, channels: u16, data_len: u32) -> [u8; 44] {
+    let block_align = channels * 2;
+    let mut h = [0u8; 44];
+    h[0..4].copy_from_slice(b"RIFF");
+    h[4..8].copy_from_slice(&(36 + data_len).to_le_bytes());
+    h[8..12].copy_from_slice(b"WAVE");
+    h[12..16].copy_from_slice(b"fmt ");
+    h[16..20].copy_from_slice(&16u32.to_le_bytes());
+    h[20..22].copy_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    h[22..24].copy_from_slice(&channels.to_le_bytes());
+    h[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    h[28..32].copy_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    h[32..34].copy_from_slice(&block_align.to_le_bytes());
+    h[34..36].copy_from_slice(&16u16.to_le_bytes());
+    h[36..40].copy_from_slice(b"data");
+    h[40..44].copy_from_slice(&data_len.to_le_bytes());
+    h
+}
+
+// 将 PCM 数据解码为单声道 i16，多声道取平均
+pub fn decode(format: &Format, data: &[u8]) -> Vec<i16> {
+    let block = format.block_align();
+    let channels = format.channels as usize;
+    let mut out = Vec::with_capacity(data.len() / block);
+
+    for frame in data.chunks_exact(block) {
+        let mut sum = 0i32;
+        for ch in 0..channels {
+            sum += match format.bits_per_sample {
+                // 8 位 PCM 为无符号数
+                8 => (frame[ch] as i32 - 128) << 8,
+                _ => i16::from_le_bytes([frame[ch * 2], frame[ch * 2 + 1]]) as i32,
+            };
+        }
+        out.push((sum / channels as i32) as i16);
+    }
+
+    out
+}
+
+// 交错排列的多声道采样混合为单声道
+pub fn downmix(input: &[i16], channels: usize) -> Vec<i16> {
+    if channels <= 1 {
+        return input.to_vec();
+    }
+    input
+        .chunks_exact(channels)
+        .map(|frame| (frame.iter().map(|s| *s as i32).sum::<i32>() / channels as i32) as i16)
+        .collect()
+}
+
+// 流式线性插值重采样，分块输入时保持相位连续
+#[derive(Debug)]
+pub struct Resampler {
+    // 16.16 定点数表示的步长
+    step: u64,
+    // 相对于 last 的源位置
+    pos: u64,
+    last: Option<i16>,
+}
+
+impl Resampler {
+    pub fn new(from: u32, to: u32) -> Self {
+        Resampler {
+            step: ((from as u64) << 16) / to as u64,
+            pos: 0,
+            last: None,
+        }
+    }
+
+    pub fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        if self.step == 1 << 16 {
+            return input.to_vec();
+        }
+        if input.is_empty() {
+            return Vec::new();
+        }
+
+        // 上一块的最后一个采样作为本块的第 0 个采样
+        let src: Vec<i16> = self
+            .last
+            .iter()
+            .copied()
+            .chain(input.iter().copied())
+            .collect();
+        let mut out = Vec::with_capacity((input.len() << 16) / self.step as usize + 1);
+        while ((self.pos >> 16) as usize) + 1 < src.len() {
+            let idx = (self.pos >> 16) as usize;
+            let frac = (self.pos & 0xFFFF) as i32;
+            let a = src[idx] as i32;
+            let b = src[idx + 1] as i32;
+            out.push((a + (((b - a) * frac) >> 16)) as i16);
+            self.pos += self.step;
+        }
+
+        self.pos -= ((src.len() - 1) as u64) << 16;
+        self.last = src.last().copied();
+        out
+    }
+}
+
+pub fn resample(input: &[i16], from: u32, to: u32) -> Vec<i16> {
+    Resampler::new(from, to).process(input)
+}
+
+// 解码并重采样到输出采样率
+pub fn to_output(format: &Format, data: &[u8], sample_rate: u32) -> Vec<i16> {
+    let pcm = decode(format, data);
+    resample(&pcm, format.sample_rate, sample_rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt_chunk(tag: u16, channels: u16, sample_rate: u32, bits: u16) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut body = Vec::new();
+        body.extend_from_slice(&tag.to_le_bytes());
+        body.extend_from_slice(&channels.to_le_bytes());
+        body.extend_from_slice(&sample_rate.to_le_bytes());
+        body.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+        body.extend_from_slice(&block_align.to_le_bytes());
+        body.extend_from_slice(&bits.to_le_bytes());
+        body
+    }
+
+    // 奇数长度的 chunk 补一个字节
+    fn riff(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut body = b"WAVE".to_vec();
+        for (id, data) in chunks {
+            body.extend_from_slice(*id);
+            body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            body.extend_from_slice(data);
+            if data.len() % 2 == 1 {
+                body.push(0);
+            }
+        }
+        let mut buf = b"RIFF".to_vec();
+        buf.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        buf.extend(body);
+        buf
+    }
+
+    fn samples(header: &Header, buf: &[u8]) -> Vec<i16> {
+        let end = (header.data_offset + header.data_len).min(buf.len());
+        decode(&header.format, &buf[header.data_offset..end])
+    }
+
+    #[test]
+    fn pcm_16bit() {
+        let data: Vec<u8> = [100i16, -200, 32767, -32768]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 1, 16000, 16)), (b"data", &data)]);
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(header.codec, Codec::Pcm);
+        assert_eq!(header.format.block_align(), 2);
+        assert_eq!(header.data_offset, 44);
+        assert_eq!(header.data_len, 8);
+        assert_eq!(samples(&header, &buf), [100, -200, 32767, -32768]);
+
+        // 双声道取平均
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 2, 16000, 16)), (b"data", &data)]);
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(header.format.block_align(), 4);
+        assert_eq!(samples(&header, &buf), [-50, 0]);
+    }
+
+    #[test]
+    fn pcm_8bit() {
+        let data = [128u8, 255, 0, 64];
+
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 1, 8000, 8)), (b"data", &data)]);
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(header.format.bits_per_sample, 8);
+        assert_eq!(samples(&header, &buf), [0, 127 << 8, -128 << 8, -64 << 8]);
+
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 2, 8000, 8)), (b"data", &data)]);
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(samples(&header, &buf), [127 << 7, -96 << 8]);
+    }
+
+    #[test]
+    fn skips_padded_and_unknown_chunks() {
+        let data = 1234i16.to_le_bytes();
+        let buf = riff(&[
+            (b"LIST", b"INFOISFT\x03\x00\x00\x00ab\x00"),
+            (b"fmt ", &fmt_chunk(1, 1, 16000, 16)),
+            (b"junk", b"odd"),
+            (b"data", &data),
+        ]);
+        let header = parse_header(&buf).unwrap();
+        assert_eq!(
+            &buf[header.data_offset - 8..header.data_offset - 4],
+            b"data"
+        );
+        assert_eq!(samples(&header, &buf), [1234]);
+    }
+
+    #[test]
+    fn invalid_headers() {
+        let fmt = fmt_chunk(1, 1, 16000, 16);
+        assert!(!is_wav(b"RIFF\0\0\0\0AVI "));
+        assert!(parse_header(b"RIFF").is_err());
+        assert!(parse_header(&riff(&[(b"data", &[0, 0])])).is_err());
+        assert!(parse_header(&riff(&[(b"fmt ", &fmt)])).is_err());
+        assert!(parse_header(&riff(&[(b"fmt ", &fmt[..12])])).is_err());
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 3, 16000, 16)), (b"data", &[])]);
+        assert!(parse_header(&buf).is_err());
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 1, 16000, 24)), (b"data", &[])]);
+        assert!(parse_header(&buf).is_err());
+        let buf = riff(&[(b"fmt ", &fmt_chunk(3, 1, 16000, 32)), (b"data", &[])]);
+        assert!(parse_header(&buf).is_err());
+
+        // 未知 chunk 声明的长度接近 u32::MAX
+        let mut buf = riff(&[(b"LIST", &[]), (b"fmt ", &fmt), (b"data", &[])]);
+        buf[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_header(&buf).is_err());
+    }
+
+    #[test]
+    fn truncated_data() {
+        let data: Vec<u8> = (0..10i16).flat_map(|s| s.to_le_bytes()).collect();
+        let buf = riff(&[(b"fmt ", &fmt_chunk(1, 1, 16000, 16)), (b"data", &data)]);
+
+        // data chunk 声明 20 字节，实际只有 7 字节，不完整的采样被丢弃
+        let truncated = &buf[..44 + 7];
+        let header = parse_header(truncated).unwrap();
+        assert_eq!(header.data_len, 20);
+        assert_eq!(samples(&header, truncated), [0, 1, 2]);
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let buf = header(16000, 2, 400);
+        let parsed = parse_header(&buf).unwrap();
+        assert_eq!(
+            parsed.format,
+            Format {
+                channels: 2,
+                sample_rate: 16000,
+                bits_per_sample: 16,
+            }
+        );
+        assert_eq!(parsed.data_offset, 44);
+        assert_eq!(parsed.data_len, 400);
+    }
+
+    #[test]
+    fn resample_length() {
+        let input: Vec<i16> = (0..8000).map(|i| (i % 100) as i16).collect();
+        assert_eq!(resample(&input, 16000, 16000), input);
+        for (from, to) in [(8000, 16000), (16000, 8000), (22050, 16000), (44100, 16000)] {
+            let out = resample(&input, from, to);
+            let expected = input.len() as f64 * to as f64 / from as f64;
+            assert!(
+                (out.len() as f64 - expected).abs() <= 2.0,
+                "{} -> {}: {} samples, expected {}",
+                from,
+                to,
+                out.len(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn resampler_chunks_match_whole() {
+        let input: Vec<i16> = (0..3000).map(|i| ((i * 37) % 2000) as i16).collect();
+        let whole = resample(&input, 22050, 16000);
+        let mut resampler = Resampler::new(22050, 16000);
+        let chunked: Vec<i16> = input
+            .chunks(317)
+            .flat_map(|chunk| resampler.process(chunk))
+            .collect();
+        assert_eq!(chunked, whole);
+    }
+
+    #[test]
+    fn downmix_stereo() {
+        assert_eq!(downmix(&[100, 300, -4, 2, 7], 2), [200, -1]);
+        assert_eq!(downmix(&[1, 2, 3], 1), [1, 2, 3]);
+    }
+}
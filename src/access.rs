@@ -0,0 +1,104 @@
+// 访问控制：凭据保存在 NVS 中，未添加凭据时不启用认证，规则见 auth.rs
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use crate::auth::{self, Credential, Denied, Kind, Scope, Store};
+use crate::global;
+use crate::nvs;
+use crate::utils;
+
+static STORE: Mutex<Option<Store>> = Mutex::new(None);
+// 每次修改凭据后加一，长连接据此判断是否需要重新校验
+static REVISION: AtomicU32 = AtomicU32::new(0);
+
+// 从 NVS 加载凭据，读取失败时不启用认证
+pub fn init() {
+    let store = load().unwrap_or_else(|e| {
+        log::error!("load credentials error: {:?}", e);
+        Store::default()
+    });
+    if store.is_enabled() {
+        log::info!("auth enabled, {} credentials", store.list().len());
+    } else {
+        log::warn!("auth disabled, no credentials");
+    }
+    *STORE.lock().unwrap() = Some(store);
+}
+
+fn load() -> anyhow::Result<Store> {
+    match nvs::read(global::AUTH_NVS_NAMESPACE, global::AUTH_NVS_KEY)? {
+        Some(buf) => Ok(serde_json::from_slice(&buf)?),
+        None => Ok(Store::default()),
+    }
+}
+
+// 校验哈希较慢，使用副本避免阻塞其它请求
+fn store() -> Store {
+    STORE.lock().unwrap().clone().unwrap_or_default()
+}
+
+pub fn authenticate(authorization: Option<&str>) -> Result<Scope, Denied> {
+    store().authenticate(authorization)
+}
+
+pub fn authorize(authorization: Option<&str>, required: Scope) -> Result<Scope, Denied> {
+    store().authorize(authorization, required)
+}
+
+pub fn revision() -> u32 {
+    REVISION.load(Ordering::Acquire)
+}
+
+pub fn list() -> Vec<auth::Info> {
+    store().list()
+}
+
+// 添加或替换凭据，未指定密码时生成 token 并返回
+pub fn add(name: &str, scope: Scope, password: Option<&str>) -> anyhow::Result<Option<String>> {
+    let mut salt = [0u8; global::AUTH_SALT_BYTES];
+    utils::random_bytes(&mut salt);
+
+    let (credential, token) = match password {
+        Some(password) => {
+            let rounds = global::AUTH_PASSWORD_ROUNDS;
+            let credential = Credential::new(name, Kind::Password, scope, password, &salt, rounds);
+            (credential, None)
+        }
+        None => {
+            let mut token = [0u8; global::AUTH_TOKEN_BYTES];
+            utils::random_bytes(&mut token);
+            let token = auth::hex(&token);
+            let credential = Credential::new(name, Kind::Token, scope, &token, &salt, 1);
+            (credential, Some(token))
+        }
+    };
+
+    update(|store| Ok(store.add(credential, global::AUTH_MAX_CREDENTIALS)?))?;
+    log::info!("credential {} saved", name);
+    Ok(token)
+}
+
+// 不存在时返回 false
+pub fn remove(name: &str) -> anyhow::Result<bool> {
+    let removed = update(|store| Ok(store.remove(name)?))?;
+    if removed {
+        log::info!("credential {} removed", name);
+    }
+    Ok(removed)
+}
+
+// 写入 NVS 成功后才更新内存中的凭据
+fn update<R>(f: impl FnOnce(&mut Store) -> anyhow::Result<R>) -> anyhow::Result<R> {
+    let mut guard = STORE.lock().unwrap();
+    let mut store = guard.clone().unwrap_or_default();
+    let result = f(&mut store)?;
+    nvs::write(
+        global::AUTH_NVS_NAMESPACE,
+        global::AUTH_NVS_KEY,
+        &serde_json::to_vec(&store)?,
+    )?;
+    *guard = Some(store);
+    REVISION.fetch_add(1, Ordering::Release);
+    Ok(result)
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+use crate::audio;
+use crate::clip;
+use crate::clock;
+use crate::earcon;
+use crate::job;
+
+// 按键、REST、语音命令等入口共用的设备动作
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", content = "arg", rename_all = "snake_case")]
+pub enum Action {
+    VolumeUp,
+    VolumeDown,
+    Stop,
+    Speak(String),
+    PlayClip(String),
+    // 重复最近一次播报
+    Replay,
+    // 播报当前时间
+    SpeakTime,
+}
+
+impl Action {
+    pub fn run(&self) {
+        log::info!("action: {:?}", self);
+        match self {
+            Action::VolumeUp => audio::volume_up(),
+            Action::VolumeDown => audio::volume_down(),
+            Action::Stop => job::stop(),
+            Action::Speak(text) => {
+                let job = job::Job::announce(text.clone(), earcon::defaults());
+                log_error(job::enqueue(job));
+            }
+            Action::PlayClip(name) => {
+                if clip::exists(name) {
+                    log_error(job::enqueue(job::Job::clip(name.clone())));
+                } else {
+                    log::warn!("clip not found: {}", name);
+                }
+            }
+            Action::Replay => match job::replay() {
+                Some(result) => log_error(result),
+                None => log::warn!("nothing to replay"),
+            },
+            Action::SpeakTime => {
+                log_error(job::enqueue_prompt(job::Job::speak(clock::now_text())));
+            }
+        }
+    }
+}
+
+fn log_error(result: anyhow::Result<u32>) {
+    if let Err(e) = result {
+        log::warn!("enqueue error: {:?}", e);
+    }
+}
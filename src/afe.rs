@@ -0,0 +1,117 @@
+use std::ffi::CString;
+use std::slice;
+
+use anyhow::bail;
+use esp_idf_svc::sys::esp_sr;
+
+use crate::global;
+
+// AFE 单次 fetch 的结果，data 在下一次 fetch 前有效
+pub struct Fetch<'a> {
+    // 处理后的单声道音频
+    pub data: &'a [i16],
+    // 检测到的唤醒词序号
+    pub wake_word: Option<i32>,
+}
+
+// esp-sr 音频前端 (AFE)：回声消除、降噪、VAD 与 WakeNet 唤醒词检测
+// feed / fetch 内部通过环形缓冲区交换数据，可在不同线程中调用
+#[derive(Clone, Copy)]
+pub struct Afe {
+    iface: *const esp_sr::esp_afe_sr_iface_t,
+    data: *mut esp_sr::esp_afe_sr_data_t,
+    models: *mut esp_sr::srmodel_list_t,
+}
+
+unsafe impl Send for Afe {}
+
+impl Afe {
+    // 输入格式 "MR"：麦克风与扬声器参考信号交错排列，启用 AEC 回声消除
+    pub fn new() -> anyhow::Result<Self> {
+        let partition_name = CString::new(global::MODEL_PARTITION).unwrap();
+        let input_format = CString::new("MR").unwrap();
+
+        unsafe {
+            let models = esp_sr::esp_srmodel_init(partition_name.as_ptr());
+            if models.is_null() {
+                bail!(
+                    "couldn't load models from partition {}",
+                    global::MODEL_PARTITION
+                );
+            }
+
+            let config = esp_sr::afe_config_init(
+                input_format.as_ptr(),
+                models,
+                esp_sr::afe_type_t_AFE_TYPE_SR,
+                esp_sr::afe_mode_t_AFE_MODE_LOW_COST,
+            );
+            if config.is_null() {
+                bail!("afe_config_init failed");
+            }
+
+            let iface = esp_sr::esp_afe_handle_from_config(config);
+            let data = ((*iface).create_from_config.unwrap())(config);
+            esp_sr::afe_config_free(config);
+            if data.is_null() {
+                bail!("afe create failed");
+            }
+            log::info!("esp afe initialized");
+
+            Ok(Afe {
+                iface,
+                data,
+                models,
+            })
+        }
+    }
+
+    // 每次 feed 需要的每声道采样点数
+    pub fn feed_chunksize(&self) -> usize {
+        unsafe { ((*self.iface).get_feed_chunksize.unwrap())(self.data) as usize }
+    }
+
+    // 每次 fetch 得到的采样点数
+    pub fn fetch_chunksize(&self) -> usize {
+        unsafe { ((*self.iface).get_fetch_chunksize.unwrap())(self.data) as usize }
+    }
+
+    // 模型分区中的模型列表，供 MultiNet 使用
+    pub fn models(&self) -> *mut esp_sr::srmodel_list_t {
+        self.models
+    }
+
+    // 聆听命令期间暂停唤醒词检测
+    pub fn set_wakenet(&self, enabled: bool) {
+        unsafe {
+            if enabled {
+                ((*self.iface).enable_wakenet.unwrap())(self.data);
+            } else {
+                ((*self.iface).disable_wakenet.unwrap())(self.data);
+            }
+        }
+    }
+
+    // pcm 为 [麦克风, 参考信号] 交错排列，长度为 feed_chunksize * 2
+    pub fn feed(&self, pcm: &[i16]) {
+        unsafe {
+            ((*self.iface).feed.unwrap())(self.data, pcm.as_ptr());
+        }
+    }
+
+    // 阻塞直到有处理好的数据
+    pub fn fetch(&self) -> Option<Fetch<'_>> {
+        unsafe {
+            let res = ((*self.iface).fetch.unwrap())(self.data);
+            if res.is_null() || (*res).ret_value == esp_sr::ESP_FAIL {
+                return None;
+            }
+
+            let data = slice::from_raw_parts((*res).data, (*res).data_size as usize / 2);
+            let wake_word = ((*res).wakeup_state == esp_sr::wakenet_state_t_WAKENET_DETECTED)
+                .then_some((*res).wake_word_index);
+
+            Some(Fetch { data, wake_word })
+        }
+    }
+}
@@ -1,93 +0,0 @@
-use esp_idf_svc::hal::{
-    gpio::AnyIOPin,
-    i2s::{config, I2sDriver, I2sTx, I2S1},
-};
-
-use std::sync::mpsc;
-
-use crate::global;
-
-pub struct Audio<'a> {
-    tx_driver: I2sDriver<'a, I2sTx>,
-}
-
-impl<'a> Audio<'a> {
-    pub fn new(
-        i2s1: I2S1,
-        dout: AnyIOPin,
-        bclk: AnyIOPin,
-        lrclk: AnyIOPin,
-        mclk: Option<AnyIOPin>,
-    ) -> Self {
-        let i2s_config = config::StdConfig::new(
-            config::Config::default().auto_clear(true),
-            config::StdClkConfig::from_sample_rate_hz(global::SAMPLE_RATE),
-            config::StdSlotConfig::philips_slot_default(
-                config::DataBitWidth::Bits16,
-                config::SlotMode::Mono,
-            ),
-            config::StdGpioConfig::default(),
-        );
-
-        let mut tx_driver =
-            I2sDriver::new_std_tx(i2s1, &i2s_config, bclk, dout, mclk, lrclk).unwrap();
-        log::info!("I2S driver initialized");
-
-        tx_driver.tx_enable().unwrap();
-        log::info!("I2S driver enabled");
-
-        Audio { tx_driver }
-    }
-
-    fn play(&mut self, data: &mut [u8]) {
-        let gain = *global::PLAY_GAIN.get().unwrap().lock().unwrap();
-        amplify_pcm_data(data, gain);
-        self.tx_driver.write_all(data, 1000).unwrap();
-    }
-
-    pub fn play_with_tx(&mut self, tx: mpsc::Receiver<&[u8]>) {
-        loop {
-            let data = tx.recv().unwrap();
-            let mut buf = data.to_owned();
-            self.play(&mut buf);
-        }
-    }
-}
-
-// 放大声音
-fn amplify_pcm_data(input: &mut [u8], gain: u8) {
-    // let gain = 1;
-    // 每次处理 2 个字节（1 个 i16 样本）的可变切片
-    for chunk in input.chunks_exact_mut(2) {
-        // 将 u8 转换为 i16（小端模式）
-        let sample = i16::from_le_bytes([chunk[0], chunk[1]]);
-        // 应用增益
-        let amplified = sample as i32 * gain as i32;
-        // 钳位处理（防止溢出 i16 范围）
-        let clamped = amplified.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
-        // 将处理后的样本写回原数组
-        let bytes = clamped.to_le_bytes();
-        chunk[0] = bytes[0];
-        chunk[1] = bytes[1];
-    }
-}
-
-pub fn volume_up() {
-    log::info!("volume_up");
-    if let Some(mutex) = global::PLAY_GAIN.get() {
-        let mut gain = mutex.lock().unwrap();
-        if *gain < 100 {
-            *gain += 1;
-        }
-    }
-}
-
-pub fn volume_down() {
-    log::info!("volume_down");
-    if let Some(mutex) = global::PLAY_GAIN.get() {
-        let mut gain = mutex.lock().unwrap();
-        if *gain > 0 {
-            *gain -= 1;
-        }
-    }
-}
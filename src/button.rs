@@ -9,6 +9,8 @@ use anyhow::Ok;
 use esp_idf_svc::hal::gpio::{AnyIOPin, Input, Pin, PinDriver};
 use esp_idf_svc::sys::EspError;
 
+use crate::event::{self, Event};
+
 #[derive(Debug, Clone, Copy)]
 pub enum ButtonType {
     K0,
@@ -16,6 +18,16 @@ pub enum ButtonType {
     Down,
 }
 
+impl ButtonType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ButtonType::K0 => "k0",
+            ButtonType::Up => "up",
+            ButtonType::Down => "down",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ButtonEvent {
     AnyEdge(ButtonType),
@@ -56,6 +68,7 @@ impl Button {
         self.btn.enable_interrupt().unwrap();
 
         let t = self.typ;
+        event::emit(Event::Button { button: t.name() });
         ButtonEvent::AnyEdge(t)
     }
 
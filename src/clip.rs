@@ -0,0 +1,234 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use embedded_svc::io::Read;
+use serde::Serialize;
+
+use crate::decoder;
+use crate::global;
+use crate::sink::{AudioSink, WavSink};
+use crate::storage;
+
+// 支持的音频格式扩展名
+const CLIP_EXTS: [&str; 2] = ["wav", "mp3"];
+
+#[derive(Debug, Serialize)]
+pub struct ClipInfo {
+    pub name: String,
+    pub size: u64,
+}
+
+// 音频库容量信息，单位字节
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub used: u64,
+    pub quota: u64,
+    pub total: u64,
+    pub free: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Library {
+    pub clips: Vec<ClipInfo>,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+pub fn init() -> anyhow::Result<()> {
+    storage::mount()?;
+    fs::create_dir_all(global::CLIP_DIR)?;
+    Ok(())
+}
+
+// 名称只允许字母、数字、'-' 和 '_'
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > global::CLIP_NAME_MAX_LEN {
+        bail!("invalid clip name length: {}", name.len());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        bail!("invalid clip name: {}", name);
+    }
+    Ok(())
+}
+
+fn path(name: &str, ext: &str) -> PathBuf {
+    PathBuf::from(global::CLIP_DIR).join(format!("{}.{}", name, ext))
+}
+
+// 查找已保存的音频文件
+fn find(name: &str) -> Option<PathBuf> {
+    CLIP_EXTS
+        .iter()
+        .map(|ext| path(name, ext))
+        .find(|p| p.exists())
+}
+
+pub fn exists(name: &str) -> bool {
+    validate_name(name).is_ok() && find(name).is_some()
+}
+
+pub fn list() -> anyhow::Result<Vec<ClipInfo>> {
+    let mut clips = Vec::new();
+    for entry in fs::read_dir(global::CLIP_DIR)? {
+        let entry = entry?;
+        let path = entry.path();
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        if !CLIP_EXTS.contains(&ext) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        clips.push(ClipInfo {
+            name: name.to_string(),
+            size: entry.metadata()?.len(),
+        });
+    }
+    clips.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(clips)
+}
+
+pub fn usage() -> anyhow::Result<Usage> {
+    let used = list()?.iter().map(|c| c.size).sum();
+    let (total, free) = storage::info()?;
+    Ok(Usage {
+        used,
+        quota: global::CLIP_QUOTA,
+        total,
+        free,
+    })
+}
+
+pub fn library() -> anyhow::Result<Library> {
+    Ok(Library {
+        clips: list()?,
+        usage: usage()?,
+    })
+}
+
+// 从 reader 流式写入 len 字节的 WAV / MP3 文件，覆盖同名音频
+pub fn save(name: &str, len: usize, reader: &mut impl Read) -> anyhow::Result<()> {
+    validate_name(name)?;
+
+    let old = check_space(name, len)?;
+
+    let mut buf = vec![0u8; 4096];
+    let mut remaining = len;
+    let mut ext = None;
+    let tmp_path = path(name, "tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+
+    let result = (|| -> anyhow::Result<()> {
+        while remaining > 0 {
+            let n = reader
+                .read(&mut buf[..remaining.min(4096)])
+                .map_err(|e| anyhow!("upload read error: {:?}", e))?;
+            if n == 0 {
+                bail!("unexpected end of upload");
+            }
+            // 首个分块必须包含完整的文件头
+            if ext.is_none() {
+                ext = Some(decoder::probe(&buf[..n])?);
+            }
+            file.write_all(&buf[..n])?;
+            remaining -= n;
+        }
+        Ok(())
+    })();
+    drop(file);
+
+    let ext = match (result, ext) {
+        (Ok(()), Some(ext)) => ext,
+        (result, _) => {
+            _ = fs::remove_file(&tmp_path);
+            return Err(result.err().unwrap_or_else(|| anyhow!("empty upload")));
+        }
+    };
+    if let Some(old) = old {
+        _ = fs::remove_file(old);
+    }
+    fs::rename(&tmp_path, path(name, ext))?;
+    log::info!("clip saved: {} ({} bytes)", name, len);
+
+    Ok(())
+}
+
+// 保存单声道 PCM (SAMPLE_RATE) 为 WAV 音频，覆盖同名音频
+pub fn save_pcm(name: &str, pcm: &[i16]) -> anyhow::Result<()> {
+    validate_name(name)?;
+    let old = check_space(name, 44 + pcm.len() * 2)?;
+
+    let tmp_path = path(name, "tmp");
+    let result = (|| -> anyhow::Result<()> {
+        let mut sink = WavSink::new(fs::File::create(&tmp_path)?, global::SAMPLE_RATE)?;
+        sink.write(pcm)?;
+        sink.finish()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        _ = fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    if let Some(old) = old {
+        _ = fs::remove_file(old);
+    }
+    fs::rename(&tmp_path, path(name, "wav"))?;
+    log::info!("clip saved: {} ({} samples)", name, pcm.len());
+
+    Ok(())
+}
+
+// 检查配额与剩余空间，返回同名的旧文件
+fn check_space(name: &str, len: usize) -> anyhow::Result<Option<PathBuf>> {
+    let usage = usage()?;
+    let old = find(name);
+    let old_size = old
+        .as_ref()
+        .and_then(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .unwrap_or(0);
+    if usage.used - old_size + len as u64 > usage.quota {
+        bail!(
+            "clip quota exceeded: {} + {} > {}",
+            usage.used,
+            len,
+            usage.quota
+        );
+    }
+    if len as u64 > usage.free + old_size {
+        bail!("not enough free space: {} > {}", len, usage.free);
+    }
+    Ok(old)
+}
+
+// 打开音频文件用于下载，返回文件与 Content-Type
+pub fn file(name: &str) -> anyhow::Result<(fs::File, &'static str)> {
+    validate_name(name)?;
+    let path = find(name).ok_or_else(|| anyhow!("clip not found: {}", name))?;
+    let content_type = match path.extension().and_then(|e| e.to_str()) {
+        Some("mp3") => "audio/mpeg",
+        _ => "audio/wav",
+    };
+    Ok((fs::File::open(path)?, content_type))
+}
+
+pub fn delete(name: &str) -> anyhow::Result<()> {
+    validate_name(name)?;
+    let path = find(name).ok_or_else(|| anyhow!("clip not found: {}", name))?;
+    fs::remove_file(path)?;
+    log::info!("clip deleted: {}", name);
+    Ok(())
+}
+
+// 打开音频流，按输出格式逐块解码
+pub fn open(name: &str) -> anyhow::Result<decoder::Stream> {
+    validate_name(name)?;
+    let path = find(name).ok_or_else(|| anyhow!("clip not found: {}", name))?;
+    decoder::Stream::open(fs::File::open(path)?, global::SAMPLE_RATE)
+}
@@ -0,0 +1,49 @@
+// 系统时间，AP 模式下没有 SNTP，由网页通过 PUT /api/time 同步
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use esp_idf_svc::sys;
+
+use crate::global;
+
+// 早于 2024-01-01 认为时间未同步
+const MIN_VALID_EPOCH: u64 = 1_704_067_200;
+
+pub fn set(epoch: u64) -> anyhow::Result<()> {
+    if epoch < MIN_VALID_EPOCH {
+        bail!("invalid time: {}", epoch);
+    }
+
+    let tv = sys::timeval {
+        tv_sec: epoch as _,
+        tv_usec: 0,
+    };
+    let ret = unsafe { sys::settimeofday(&tv, std::ptr::null()) };
+    if ret != 0 {
+        bail!("settimeofday failed: {}", ret);
+    }
+    log::info!("time synced: {}", epoch);
+    Ok(())
+}
+
+// 当前 UTC 时间，未同步时返回 None
+pub fn now() -> Option<u64> {
+    let epoch = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+    (epoch >= MIN_VALID_EPOCH).then_some(epoch)
+}
+
+// 播报用的当前时间文本，按 global::TIME_ZONE_OFFSET_MINUTES 转换为本地时间
+pub fn now_text() -> String {
+    let Some(epoch) = now() else {
+        return global::TTS_TEXT_TIME_UNKNOWN.to_string();
+    };
+
+    let local = epoch as i64 + global::TIME_ZONE_OFFSET_MINUTES * 60;
+    let minutes = local.rem_euclid(24 * 3600) / 60;
+    let (hour, minute) = (minutes / 60, minutes % 60);
+    if minute == 0 {
+        format!("现在是{}点整", hour)
+    } else {
+        format!("现在是{}点{}分", hour, minute)
+    }
+}
@@ -0,0 +1,138 @@
+use std::thread::sleep;
+use std::time::Duration;
+
+use esp_idf_svc::hal::{
+    delay::BLOCK,
+    gpio::AnyIOPin,
+    i2c::{I2c, I2cConfig, I2cDriver},
+    peripheral::Peripheral,
+    prelude::*,
+};
+
+use crate::i2s::I2sSink;
+use crate::sink::AudioSink;
+
+// 音频编解码芯片，通过 I2C 配置寄存器，音频数据仍走 I2S
+// 两者均工作在 I2S 从模式，MCLK = 256 * SAMPLE_RATE
+#[allow(dead_code)] // 只会启用其中一种芯片
+#[derive(Debug, Clone, Copy)]
+pub enum Chip {
+    Es8311,
+    Es8388,
+}
+
+#[cfg(feature = "es8388")]
+pub const CHIP: Chip = Chip::Es8388;
+#[cfg(not(feature = "es8388"))]
+pub const CHIP: Chip = Chip::Es8311;
+
+// (寄存器, 值)，值为 None 时表示延时 10ms
+type Init = &'static [(u8, Option<u8>)];
+
+// DAC 播放通路，16kHz / 16bit / I2S
+const ES8311_INIT: Init = &[
+    (0x00, Some(0x1F)), // reset
+    (0x00, None),
+    (0x00, Some(0x00)),
+    (0x00, Some(0x80)), // power on, slave mode
+    (0x01, Some(0x3F)), // MCLK from pin, enable all clocks
+    (0x02, Some(0x00)), // pre_div = 1, pre_mult = 1
+    (0x03, Some(0x10)), // adc osr
+    (0x04, Some(0x10)), // dac osr
+    (0x05, Some(0x00)), // adc / dac div = 1
+    (0x06, Some(0x03)), // bclk div = 4
+    (0x07, Some(0x00)), // lrck div = 256
+    (0x08, Some(0xFF)),
+    (0x09, Some(0x0C)), // SDP in: I2S, 16bit
+    (0x0A, Some(0x0C)), // SDP out: I2S, 16bit
+    (0x0B, Some(0x00)),
+    (0x0C, Some(0x00)),
+    (0x0D, Some(0x01)), // power up analog
+    (0x0E, Some(0x02)),
+    (0x12, Some(0x00)), // power up DAC
+    (0x13, Some(0x10)), // enable output to HP drive
+    (0x1C, Some(0x6A)),
+    (0x37, Some(0x08)), // bypass DAC equalizer
+    (0x32, Some(0xBF)), // DAC volume 0dB
+    (0x31, Some(0x00)), // unmute
+];
+
+// DAC 播放通路，LOUT1/ROUT1 与 LOUT2/ROUT2 均输出
+const ES8388_INIT: Init = &[
+    (0x19, Some(0x04)), // mute DAC
+    (0x01, Some(0x50)),
+    (0x02, Some(0x00)), // power up
+    (0x35, Some(0xA0)), // disable internal DLL
+    (0x37, Some(0xD0)),
+    (0x39, Some(0xD0)),
+    (0x08, Some(0x00)), // slave mode
+    (0x04, Some(0xC0)), // power down DAC
+    (0x00, Some(0x12)),
+    (0x17, Some(0x18)), // I2S, 16bit
+    (0x18, Some(0x02)), // MCLK / LRCK = 256
+    (0x26, Some(0x00)),
+    (0x27, Some(0x90)), // left DAC to left mixer
+    (0x2A, Some(0x90)), // right DAC to right mixer
+    (0x2B, Some(0x80)),
+    (0x2D, Some(0x00)),
+    (0x1A, Some(0x00)), // DAC volume 0dB
+    (0x1B, Some(0x00)),
+    (0x2E, Some(0x1E)), // output volume
+    (0x2F, Some(0x1E)),
+    (0x30, Some(0x1E)),
+    (0x31, Some(0x1E)),
+    (0x04, Some(0x3C)), // power up DAC and outputs
+    (0x02, None),
+    (0x19, Some(0x00)), // unmute
+];
+
+impl Chip {
+    fn address(&self) -> u8 {
+        match self {
+            Chip::Es8311 => 0x18,
+            Chip::Es8388 => 0x10,
+        }
+    }
+
+    fn init_sequence(&self) -> Init {
+        match self {
+            Chip::Es8311 => ES8311_INIT,
+            Chip::Es8388 => ES8388_INIT,
+        }
+    }
+}
+
+pub struct CodecSink {
+    i2s: I2sSink,
+    _i2c: I2cDriver<'static>,
+}
+
+impl CodecSink {
+    pub fn new(
+        chip: Chip,
+        i2c: impl Peripheral<P = impl I2c> + 'static,
+        sda: AnyIOPin,
+        scl: AnyIOPin,
+        i2s: I2sSink,
+    ) -> anyhow::Result<Self> {
+        let config = I2cConfig::new().baudrate(100.kHz().into());
+        let mut i2c = I2cDriver::new(i2c, sda, scl, &config)?;
+
+        let addr = chip.address();
+        for (reg, value) in chip.init_sequence() {
+            match value {
+                Some(value) => i2c.write(addr, &[*reg, *value], BLOCK)?,
+                None => sleep(Duration::from_millis(10)),
+            }
+        }
+        log::info!("codec {:?} initialized", chip);
+
+        Ok(CodecSink { i2s, _i2c: i2c })
+    }
+}
+
+impl AudioSink for CodecSink {
+    fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+        self.i2s.write(pcm)
+    }
+}
@@ -0,0 +1,87 @@
+// 语音命令表：MultiNet 识别的短语与设备动作的对应关系
+// 保存在存储分区中，文件不存在时使用默认命令表
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::action::Action;
+use crate::global;
+
+// 命令表被修改，识别线程需要重新加载
+static UPDATED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Command {
+    // 中文模型使用空格分隔的拼音，例如 "yin liang jia da"
+    pub phrase: String,
+    // {"action": "volume_up"} 或 {"action": "speak", "arg": "你好"}
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+impl Command {
+    fn new(phrase: &str, action: Action) -> Self {
+        Command {
+            phrase: phrase.to_string(),
+            action,
+        }
+    }
+}
+
+pub fn defaults() -> Vec<Command> {
+    vec![
+        Command::new("yin liang jia da", Action::VolumeUp),
+        Command::new("yin liang jian xiao", Action::VolumeDown),
+        Command::new("ting zhi bo bao", Action::Stop),
+        Command::new("chong fu yi bian", Action::Replay),
+        Command::new("xian zai ji dian", Action::SpeakTime),
+    ]
+}
+
+pub fn validate(commands: &[Command]) -> anyhow::Result<()> {
+    if commands.is_empty() || commands.len() > global::MAX_COMMANDS {
+        bail!("expected 1 to {} commands", global::MAX_COMMANDS);
+    }
+    for command in commands {
+        let phrase = &command.phrase;
+        if phrase.trim().is_empty()
+            || phrase.len() > global::COMMAND_PHRASE_MAX_LEN
+            || !phrase.chars().all(|c| c.is_ascii_lowercase() || c == ' ')
+        {
+            bail!("invalid phrase: {:?}", phrase);
+        }
+    }
+    Ok(())
+}
+
+// 读取命令表，文件不存在或格式错误时返回默认命令表
+pub fn list() -> Vec<Command> {
+    let Ok(buf) = fs::read(global::COMMANDS_PATH) else {
+        return defaults();
+    };
+    match serde_json::from_slice::<Vec<Command>>(&buf) {
+        Ok(commands) if validate(&commands).is_ok() => commands,
+        _ => {
+            log::warn!(
+                "invalid command table {}, use defaults",
+                global::COMMANDS_PATH
+            );
+            defaults()
+        }
+    }
+}
+
+pub fn save(commands: &[Command]) -> anyhow::Result<()> {
+    validate(commands)?;
+    fs::write(global::COMMANDS_PATH, serde_json::to_vec(commands)?)?;
+    UPDATED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// 命令表是否在上次调用后被修改
+pub fn take_updated() -> bool {
+    UPDATED.swap(false, Ordering::Relaxed)
+}
@@ -0,0 +1,142 @@
+// 播报前后的提示音：内置音调、内嵌音频或音频库片段
+// 字符串形式为 "none"、"tone:ding_dong"、"asset:chime" 或 "clip:名称"
+
+use std::fmt;
+use std::fs;
+use std::io::Cursor;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::clip;
+use crate::decoder;
+use crate::global;
+use crate::tone;
+
+// 内嵌在固件中的提示音
+const ASSETS: &[(&str, &[u8])] = &[("chime", include_bytes!("../assets/chime.wav"))];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Earcon {
+    #[default]
+    None,
+    Tone(String),
+    Asset(String),
+    Clip(String),
+}
+
+impl TryFrom<String> for Earcon {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "none" {
+            return Ok(Earcon::None);
+        }
+        let (kind, name) = value
+            .split_once(':')
+            .ok_or_else(|| anyhow!("invalid earcon: {:?}", value))?;
+        let name = name.to_string();
+        match kind {
+            "tone" => Ok(Earcon::Tone(name)),
+            "asset" => Ok(Earcon::Asset(name)),
+            "clip" => Ok(Earcon::Clip(name)),
+            _ => bail!("invalid earcon: {:?}", value),
+        }
+    }
+}
+
+impl fmt::Display for Earcon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Earcon::None => write!(f, "none"),
+            Earcon::Tone(name) => write!(f, "tone:{}", name),
+            Earcon::Asset(name) => write!(f, "asset:{}", name),
+            Earcon::Clip(name) => write!(f, "clip:{}", name),
+        }
+    }
+}
+
+impl From<Earcon> for String {
+    fn from(earcon: Earcon) -> Self {
+        earcon.to_string()
+    }
+}
+
+impl Earcon {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            Earcon::None => {}
+            Earcon::Tone(name) => {
+                tone::preset(name).ok_or_else(|| anyhow!("tone not found: {}", name))?;
+            }
+            Earcon::Asset(name) => {
+                asset(name).ok_or_else(|| anyhow!("asset not found: {}", name))?;
+            }
+            Earcon::Clip(name) => {
+                if !clip::exists(name) {
+                    bail!("clip not found: {}", name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // 打开提示音的解码流，None 表示不播放
+    pub fn open(&self) -> anyhow::Result<Option<decoder::Stream>> {
+        match self {
+            Earcon::None => Ok(None),
+            Earcon::Tone(name) => {
+                let sequence =
+                    tone::preset(name).ok_or_else(|| anyhow!("tone not found: {}", name))?;
+                let pcm = sequence.render(global::SAMPLE_RATE);
+                Ok(Some(decoder::Stream::from_pcm(pcm, global::SAMPLE_RATE)))
+            }
+            Earcon::Asset(name) => {
+                let data = asset(name).ok_or_else(|| anyhow!("asset not found: {}", name))?;
+                decoder::Stream::open(Cursor::new(data), global::SAMPLE_RATE).map(Some)
+            }
+            Earcon::Clip(name) => clip::open(name).map(Some),
+        }
+    }
+}
+
+fn asset(name: &str) -> Option<&'static [u8]> {
+    ASSETS
+        .iter()
+        .find(|(asset, _)| *asset == name)
+        .map(|(_, data)| *data)
+}
+
+// 播报前后的提示音，作为同一个播报 ID 播放
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Earcons {
+    #[serde(default)]
+    pub pre: Earcon,
+    #[serde(default)]
+    pub post: Earcon,
+}
+
+impl Earcons {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        self.pre.validate()?;
+        self.post.validate()
+    }
+}
+
+// 设备默认的提示音，文件不存在时不播放提示音
+pub fn defaults() -> Earcons {
+    let Ok(buf) = fs::read(global::EARCONS_PATH) else {
+        return Earcons::default();
+    };
+    serde_json::from_slice(&buf).unwrap_or_else(|e| {
+        log::warn!("invalid earcons {}: {:?}", global::EARCONS_PATH, e);
+        Earcons::default()
+    })
+}
+
+pub fn save(earcons: &Earcons) -> anyhow::Result<()> {
+    earcons.validate()?;
+    fs::write(global::EARCONS_PATH, serde_json::to_vec(earcons)?)?;
+    Ok(())
+}
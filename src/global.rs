@@ -1,32 +1,179 @@
-use std::sync::{Mutex, OnceLock};
+use crate::mic::MicFormat;
+
+// 音频管线的全局状态与常量 (SAMPLE_RATE、PLAY_GAIN、MIXER 等) 及 init 定义在 etts-core 中
+pub use etts_core::global::*;
 
 // audio
-// 录音/播放 采样率 HZ
-pub const SAMPLE_RATE: u32 = 16000;
-// paly gain
-pub static PLAY_GAIN: OnceLock<Mutex<u8>> = OnceLock::new();
+// 播报队列的默认长度，超过时 /api/tts 等返回 503
+pub const QUEUE_MAX_LEN: usize = 16;
+// PUT /api/queue 可设置的最大长度
+pub const QUEUE_MAX_LEN_LIMIT: usize = 64;
+// GET /api/queue 中文本预览的字数
+pub const QUEUE_PREVIEW_CHARS: usize = 24;
+// 音频线程队列长度，限制解码时缓存的数据量
+pub const AUDIO_QUEUE_LEN: usize = 8;
+
+// stream
+// 同时监听扬声器输出的客户端数量上限
+pub const STREAM_MAX_LISTENERS: usize = 2;
+// 每个监听者缓存的帧数 (约 0.5s)
+pub const STREAM_QUEUE_LEN: usize = 32;
+// 每个 WebSocket 二进制帧的采样数
+pub const STREAM_CHUNK_SAMPLES: usize = 1024;
+// 每 5s 丢帧超过该值 (约 1s 的音频) 时断开监听者
+pub const STREAM_WINDOW_MS: u64 = 5000;
+pub const STREAM_MAX_DROPPED_FRAMES: usize = 62;
+pub const STREAM_STACK_SIZE: usize = 4096;
+
+// mic
+// 麦克风数据格式，INMP441 等 24 位 I2S 麦克风
+pub const MIC_FORMAT: MicFormat = MicFormat {
+    sample_rate: 16000,
+    bits: 32,
+    right_channel: false,
+    gain_shift: 2,
+};
+// 每次读取的采样点数
+pub const MIC_FRAME_SAMPLES: usize = 512;
+// 每个订阅者缓存的帧数
+pub const MIC_QUEUE_LEN: usize = 16;
+// 最长录音时间
+pub const MAX_RECORD_SECONDS: u32 = 10;
+// 回环自检测试音频率与时长
+pub const LOOPBACK_TONE_HZ: u32 = 1000;
+pub const LOOPBACK_TONE_MS: u32 = 500;
+pub const LOOPBACK_RECORD_MS: u32 = 1500;
+
+// wake word
+// esp-sr 模型分区名称
+pub const MODEL_PARTITION: &str = "model";
+// 唤醒后播放的提示音，音频库中不存在时播报 WAKE_ACK_TEXT
+pub const WAKE_ACK_CLIP: &str = "wake";
+pub const WAKE_ACK_TEXT: &str = "我在";
+// 唤醒后等待命令词的时长
+pub const WAKE_LISTEN_MS: u64 = 6000;
+// AEC 参考信号最多缓存的采样点数，超出时丢弃最旧的数据 (500ms)
+pub const AEC_REF_MAX_SAMPLES: usize = 8000;
+// AFE fetch 线程栈大小
+pub const WAKE_STACK_SIZE: usize = 8192;
+// 语音命令表，不存在时使用 command::defaults
+pub const COMMANDS_PATH: &str = "/storage/commands.json";
+pub const MAX_COMMANDS: usize = 32;
+pub const COMMAND_PHRASE_MAX_LEN: usize = 63;
 
 // lvgl
 // LCD display
 pub const DISPLAY_WIDTH: usize = 240;
 pub const DISPLAY_HEIGHT: usize = 240;
+// 屏幕刷新间隔，VU 表按此间隔更新
+pub const UI_REFRESH_MS: u64 = 50;
+// 唤醒后 / 聆听结束时显示的文字
+pub const UI_TEXT_LISTENING: &str = "聆听中...";
+pub const UI_TEXT_IDLE: &str = "等待唤醒";
 
 // tts
 //  TTS TEXT
 pub const TTS_TEXT_HELLO: &str = "欢迎使用文字转转语音示例";
+pub const TTS_TEXT_TIME_UNKNOWN: &str = "时间未同步";
+
+// 设备默认的播报前后提示音
+pub const EARCONS_PATH: &str = "/storage/earcons.json";
+
+// clock
+// 本地时区相对 UTC 的偏移，默认东八区
+pub const TIME_ZONE_OFFSET_MINUTES: i64 = 8 * 60;
 
 // server
 // 嵌入index.html到二进制文件中
 pub const INDEX_HTML: &str = include_str!("../assets/index.html");
 // Max payload length
 pub const MAX_LEN: usize = 128;
+// /api/play 上传音频的最大长度
+pub const MAX_PLAY_LEN: usize = 512 * 1024;
+// /api/commands 命令表的最大长度
+pub const MAX_COMMANDS_LEN: usize = 4096;
+// /api/tone 请求的最大长度及生成音调的最大时长
+pub const MAX_TONE_LEN: usize = 2048;
+pub const MAX_TONE_MS: u64 = 10000;
+// /api/v1 请求体及播报文本的最大长度
+pub const MAX_API_LEN: usize = 2048;
+pub const MAX_TEXT_LEN: usize = 512;
+// 最多注册的 URI handler 数量
+pub const MAX_URI_HANDLERS: usize = 48;
+
+// auth
+// 凭据保存在 NVS 中的命名空间和键名
+pub const AUTH_NVS_NAMESPACE: &str = "auth";
+pub const AUTH_NVS_KEY: &str = "credentials";
+pub const AUTH_MAX_CREDENTIALS: usize = 8;
+// 密码哈希的 PBKDF2 迭代次数，token 为随机数只迭代一次
+pub const AUTH_PASSWORD_ROUNDS: u32 = 1000;
+pub const AUTH_TOKEN_BYTES: usize = 16;
+pub const AUTH_SALT_BYTES: usize = 16;
+// 401 响应的 WWW-Authenticate，使用 Bearer 避免浏览器弹出 Basic 登录框
+pub const AUTH_CHALLENGE: &str = "Bearer realm=\"esp32s3-tts-demo\"";
+
+// tls
+// HTTPS 设置和证书保存在 NVS 中的命名空间和键名
+pub const TLS_NVS_NAMESPACE: &str = "tls";
+pub const TLS_SETTINGS_KEY: &str = "settings";
+pub const TLS_GENERATED_KEY: &str = "generated";
+pub const TLS_CUSTOM_KEY: &str = "custom";
+// PUT /api/v1/tls/cert 请求体的最大长度
+pub const MAX_CERT_LEN: usize = 18 * 1024;
+// HTTP / HTTPS 服务器端口，ctrl 为 httpd 内部使用的 UDP 控制端口
+pub const HTTP_PORT: u16 = 80;
+pub const HTTPS_PORT: u16 = 443;
+pub const HTTP_CTRL_PORT: u16 = 32768;
+// HTTPS 启用时 80 端口的重定向服务器，控制端口不能与主服务器相同
+pub const REDIRECT_CTRL_PORT: u16 = 32769;
+// HTTP 服务器占用的端口，TCP / UDP 文本协议不能使用
+pub const RESERVED_PORTS: &[u16] = &[HTTP_PORT, HTTPS_PORT, HTTP_CTRL_PORT, REDIRECT_CTRL_PORT];
+// POST /api/v1/restart 返回响应后等待的时间
+pub const RESTART_DELAY_MS: u64 = 500;
+
+// mqtt
+// 设置保存在 NVS 中的命名空间和键名
+pub const MQTT_NVS_NAMESPACE: &str = "mqtt";
+pub const MQTT_SETTINGS_KEY: &str = "settings";
+// 收发缓冲区大小，命令消息不能超过该长度
+pub const MQTT_BUFFER_SIZE: usize = 2048;
+// 断开后重连的间隔
+pub const MQTT_RECONNECT_MS: u64 = 5000;
+pub const MQTT_CONN_STACK_SIZE: usize = 4096;
+// 发布内存、运行时间和信号强度的间隔
+pub const MQTT_TELEMETRY_MS: u64 = 60_000;
+// Home Assistant 中显示的设备型号
+pub const DEVICE_MODEL: &str = "ESP32-S3";
+
+// socket
+// TCP / UDP 文本协议设置保存在 NVS 中的命名空间和键名
+pub const SOCKET_NVS_NAMESPACE: &str = "socket";
+pub const SOCKET_SETTINGS_KEY: &str = "settings";
+// 同时连接的 TCP 客户端数量上限
+pub const SOCKET_MAX_CONNECTIONS: usize = 4;
+pub const SOCKET_STACK_SIZE: usize = 4096;
+// TCP 连接超过该时长没有收到数据时断开
+pub const SOCKET_IDLE_TIMEOUT_MS: u64 = 60_000;
+// UDP 数据报开头 "!auth ..." 行的最大长度
+pub const SOCKET_AUTH_MAX_LEN: usize = 128;
+
+// storage
+// 数据分区名称及挂载点
+pub const STORAGE_PARTITION: &str = "storage";
+pub const STORAGE_BASE_PATH: &str = "/storage";
+// 音频库目录
+pub const CLIP_DIR: &str = "/storage/clips";
+// 音频库配额
+pub const CLIP_QUOTA: u64 = 768 * 1024;
+pub const CLIP_NAME_MAX_LEN: usize = 32;
+
+// button
+// K0 按键播放的音频名称
+pub const BUTTON_K0_CLIP: &str = "k0";
 // Need lots of stack to parse JSON
 pub const STACK_SIZE: usize = 10240;
 
 // wifi
 // wifi ap name
 pub const WIFI_AP_NAME: &str = "esp32s3-tts-demo";
-
-pub fn init() {
-    PLAY_GAIN.set(Mutex::new(1)).unwrap();
-}
@@ -0,0 +1,57 @@
+use esp_idf_svc::hal::{
+    gpio::AnyIOPin,
+    i2s::{config, I2s, I2sDriver, I2sTx},
+    peripheral::Peripheral,
+};
+use esp_idf_svc::sys::ESP_ERR_TIMEOUT;
+
+use crate::global;
+use crate::sink::{self, AudioSink};
+
+// I2S 功放输出，例如 MAX98357A
+pub struct I2sSink {
+    tx_driver: I2sDriver<'static, I2sTx>,
+}
+
+impl I2sSink {
+    pub fn new(
+        i2s: impl Peripheral<P = impl I2s> + 'static,
+        dout: AnyIOPin,
+        bclk: AnyIOPin,
+        lrclk: AnyIOPin,
+        mclk: Option<AnyIOPin>,
+    ) -> anyhow::Result<Self> {
+        let i2s_config = config::StdConfig::new(
+            config::Config::default().auto_clear(true),
+            config::StdClkConfig::from_sample_rate_hz(global::SAMPLE_RATE),
+            config::StdSlotConfig::philips_slot_default(
+                config::DataBitWidth::Bits16,
+                config::SlotMode::Mono,
+            ),
+            config::StdGpioConfig::default(),
+        );
+
+        let mut tx_driver = I2sDriver::new_std_tx(i2s, &i2s_config, bclk, dout, mclk, lrclk)?;
+        log::info!("I2S driver initialized");
+
+        tx_driver.tx_enable()?;
+        log::info!("I2S driver enabled");
+
+        Ok(I2sSink { tx_driver })
+    }
+}
+
+impl AudioSink for I2sSink {
+    fn write(&mut self, pcm: &[i16]) -> anyhow::Result<()> {
+        match self.tx_driver.write_all(pcm_as_bytes(pcm), 1000) {
+            Ok(()) => Ok(()),
+            Err(e) if e.code() == ESP_ERR_TIMEOUT => Err(sink::Timeout.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+// i16 采样按小端字节序写入 I2S（ESP32 为小端）
+fn pcm_as_bytes(pcm: &[i16]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(pcm.as_ptr() as *const u8, std::mem::size_of_val(pcm)) }
+}
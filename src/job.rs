@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::clock;
+use crate::earcon::Earcons;
+use crate::event::{self, Event};
+use crate::global;
+use crate::queue::{Entry, Queue};
+
+pub use crate::queue::Full;
+
+// ID 小于该值的播报均已被停止，SURVIVORS 中的除外
+static STOP_BEFORE: AtomicU32 = AtomicU32::new(0);
+// 上次 stop() 时仍在队列中等待的任务，不受 STOP_BEFORE 影响
+static SURVIVORS: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+// 等待 TTS 线程处理的任务，播报 ID 在入队时分配
+static QUEUE: Queue<Job> = Queue::new(global::QUEUE_MAX_LEN);
+// 最近一次文本或音频库播报，用于 "重复一遍"
+static LAST: Mutex<Option<Job>> = Mutex::new(None);
+
+pub const PRIORITY_NORMAL: u8 = 0;
+
+// GET /api/queue 中的一项
+#[derive(Debug, Serialize)]
+pub struct Pending {
+    pub id: u32,
+    // speak, play 或 clip
+    pub kind: &'static str,
+    // 文本开头或音频名称
+    pub preview: String,
+    pub priority: u8,
+    // 入队时间 (UTC 秒)，时间未同步时为 None
+    pub enqueued_at: Option<u64>,
+    pub waiting_ms: u64,
+}
+
+// 播报任务：文本合成、直接播放 PCM 或播放音频库中的片段
+// 文本合成前后可带提示音，与文本使用同一个播报 ID
+#[derive(Debug, Clone)]
+pub enum Job {
+    Speak { text: String, earcons: Earcons },
+    Play { pcm: Vec<i16> },
+    Clip { name: String },
+}
+
+impl Job {
+    pub fn speak(text: String) -> Self {
+        Job::announce(text, Earcons::default())
+    }
+
+    pub fn announce(text: String, earcons: Earcons) -> Self {
+        Job::Speak { text, earcons }
+    }
+
+    pub fn play(pcm: Vec<i16>) -> Self {
+        Job::Play { pcm }
+    }
+
+    pub fn clip(name: String) -> Self {
+        Job::Clip { name }
+    }
+
+    fn preview(&self) -> (&'static str, String) {
+        match self {
+            Job::Speak { text, .. } => {
+                let mut preview: String = text.chars().take(global::QUEUE_PREVIEW_CHARS).collect();
+                if preview.len() < text.len() {
+                    preview.push('…');
+                }
+                ("speak", preview)
+            }
+            Job::Play { pcm, .. } => (
+                "play",
+                format!("{}ms", pcm.len() as u64 * 1000 / global::SAMPLE_RATE as u64),
+            ),
+            Job::Clip { name, .. } => ("clip", name.clone()),
+        }
+    }
+}
+
+// 停止正在合成或播放的播报，队列中等待的任务之后照常播放
+pub fn stop() {
+    let mut survivors = SURVIVORS.lock().unwrap();
+    // 与入队使用同一个锁，已分配 ID 的任务要么在队列中，要么已被 TTS 线程取出
+    let (pending, id) = QUEUE.snapshot(|entry| entry.id);
+    *survivors = pending;
+    log::info!("stop before id {}, {} pending kept", id, survivors.len());
+    STOP_BEFORE.store(id, Ordering::Relaxed);
+}
+
+// 停止当前的播报并清空队列
+pub fn stop_all() {
+    stop();
+    flush();
+}
+
+pub fn is_stopped(id: u32) -> bool {
+    id < STOP_BEFORE.load(Ordering::Relaxed) && !SURVIVORS.lock().unwrap().contains(&id)
+}
+
+// 将任务放入队列并发出 Queued 事件，队列已满时返回 Full 错误
+pub fn enqueue(job: Job) -> anyhow::Result<u32> {
+    enqueue_with_priority(job, PRIORITY_NORMAL)
+}
+
+pub fn enqueue_with_priority(job: Job, priority: u8) -> anyhow::Result<u32> {
+    if matches!(job, Job::Speak { .. } | Job::Clip { .. }) {
+        *LAST.lock().unwrap() = Some(job.clone());
+    }
+    push(job, priority)
+}
+
+// 提示音、报时等不需要 "重复一遍" 的播报
+pub fn enqueue_prompt(job: Job) -> anyhow::Result<u32> {
+    push(job, PRIORITY_NORMAL)
+}
+
+fn push(job: Job, priority: u8) -> anyhow::Result<u32> {
+    let id = QUEUE.push(priority, job)?;
+    event::emit(Event::Queued { id });
+    Ok(id)
+}
+
+// TTS 线程取出下一个任务及其播报 ID，队列为空时阻塞
+pub fn next() -> (u32, Job) {
+    let entry = QUEUE.pop();
+    (entry.id, entry.item)
+}
+
+// 以新的 ID 重新播报最近一次的文本或音频库片段
+pub fn replay() -> Option<anyhow::Result<u32>> {
+    let job = LAST.lock().unwrap().clone()?;
+    Some(enqueue(job))
+}
+
+// 按播放顺序列出等待中的任务
+pub fn pending() -> Vec<Pending> {
+    let now = clock::now();
+    QUEUE.list(|entry: &Entry<Job>| {
+        let waiting = entry.enqueued.elapsed();
+        let (kind, preview) = entry.item.preview();
+        Pending {
+            id: entry.id,
+            kind,
+            preview,
+            priority: entry.priority,
+            enqueued_at: now.map(|now| now.saturating_sub(waiting.as_secs())),
+            waiting_ms: waiting.as_millis() as u64,
+        }
+    })
+}
+
+pub fn pending_len() -> usize {
+    QUEUE.count()
+}
+
+// 取消等待中的任务，正在播放的任务使用 stop()
+pub fn cancel(id: u32) -> bool {
+    let Some(entry) = QUEUE.remove(id) else {
+        return false;
+    };
+    log::info!("cancel {}", entry.id);
+    event::emit(Event::Stopped { id: entry.id });
+    true
+}
+
+// 清空队列，返回移除的任务数
+pub fn flush() -> usize {
+    let entries = QUEUE.clear();
+    for entry in &entries {
+        event::emit(Event::Stopped { id: entry.id });
+    }
+    entries.len()
+}
+
+pub fn max_len() -> usize {
+    QUEUE.max_len()
+}
+
+pub fn set_max_len(max_len: usize) {
+    QUEUE.set_max_len(max_len);
+}
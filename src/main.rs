@@ -3,14 +3,41 @@ use esp_idf_svc::hal::{gpio::AnyIOPin, i2s::I2S1};
 use std::sync::mpsc;
 use std::thread::spawn;
 
-mod audio;
+// 不依赖 esp-idf 的模块，crate::audio 等路径保持不变
+use etts_core::{
+    api, audio, auth, broker, cert, decoder, discovery, effect, event, line, loopback,
+    loudness, markup, mixer, protocol, queue, sink, telemetry, tone, trim, wav,
+};
+
+mod access;
+mod action;
+mod afe;
 mod button;
+mod clip;
+mod clock;
+#[cfg(any(feature = "es8311", feature = "es8388"))]
+mod codec;
+mod command;
+mod earcon;
 mod global;
+mod i2s;
+mod job;
+mod mic;
+mod mqtt;
+mod multinet;
+mod nvs;
 mod server;
+mod socket;
+mod status;
+mod storage;
+mod stream;
+mod tls;
 mod tts;
 mod ui_lvgl;
 mod utils;
+mod wakeword;
 mod wifi;
+mod ws;
 
 fn main() -> anyhow::Result<()> {
     // It is necessary to call this function once. Otherwise some patches to the runtime
@@ -22,12 +49,17 @@ fn main() -> anyhow::Result<()> {
 
     let peripherals = esp_idf_svc::hal::prelude::Peripherals::take().unwrap();
     let sysloop = esp_idf_svc::eventloop::EspSystemEventLoop::take()?;
+    let nvs_partition = esp_idf_svc::nvs::EspDefaultNvsPartition::take()?;
     utils::log_heap();
 
     log::info!("init esp32s3 tts demo");
 
     global::init();
 
+    // load api credentials, auth stays disabled until one is added
+    nvs::init(nvs_partition);
+    access::init();
+
     // init ui
     log::info!("init ui");
     let mut ui = ui_lvgl::UI::new();
@@ -35,6 +67,12 @@ fn main() -> anyhow::Result<()> {
 
     utils::print_partitions();
 
+    // init clip library
+    log::info!("init clip library");
+    if let Err(e) = clip::init() {
+        log::error!("clip library init error: {:?}", e);
+    }
+
     // init button
     log::info!("init button");
     let mut btn_k0 = button::Button::new(peripherals.pins.gpio0.into(), button::ButtonType::K0)?;
@@ -45,21 +83,19 @@ fn main() -> anyhow::Result<()> {
     spawn(move || loop {
         log::info!("wait_for_any_edge btn_up");
         let e = btn_up.wait_for_any_edge();
-        audio::volume_up();
+        action::Action::VolumeUp.run();
         log::info!("wait_for_any_edge {:?}", e);
     });
 
     spawn(move || loop {
         log::info!("wait_for_any_edge btn_down");
         let e = btn_down.wait_for_any_edge();
-        audio::volume_down();
+        action::Action::VolumeDown.run();
         log::info!("wait_for_any_edge {:?}", e);
     });
 
-    // tts text channel
-    let (tx, rx) = mpsc::channel();
     // tts text to sound channel
-    let (tx2, rx2) = mpsc::channel();
+    let (tx2, rx2) = mpsc::sync_channel(global::AUDIO_QUEUE_LEN);
     // ui show text channel
     let (tx3, rx3) = mpsc::channel();
 
@@ -69,19 +105,65 @@ fn main() -> anyhow::Result<()> {
     let dout: AnyIOPin = peripherals.pins.gpio7.into();
     let bclk: AnyIOPin = peripherals.pins.gpio15.into();
     let lrclk: AnyIOPin = peripherals.pins.gpio16.into();
+    #[cfg(not(any(feature = "es8311", feature = "es8388")))]
     let mclk: Option<AnyIOPin> = None;
-
-    let mut audio = audio::Audio::new(i2s1, dout, bclk, lrclk, mclk);
+    #[cfg(any(feature = "es8311", feature = "es8388"))]
+    let mclk: Option<AnyIOPin> = Some(peripherals.pins.gpio42.into());
+
+    let audio_sink = i2s::I2sSink::new(i2s1, dout, bclk, lrclk, mclk);
+    // codec boards configure the codec over I2C, audio data still goes through I2S
+    #[cfg(any(feature = "es8311", feature = "es8388"))]
+    let audio_sink = audio_sink.and_then(|i2s| {
+        codec::CodecSink::new(
+            codec::CHIP,
+            peripherals.i2c0,
+            peripherals.pins.gpio1.into(),
+            peripherals.pins.gpio2.into(),
+            i2s,
+        )
+    });
+    let audio_sink: Box<dyn sink::AudioSink> = match audio_sink {
+        Ok(audio_sink) => Box::new(audio_sink),
+        Err(e) => {
+            // keep the rest of the firmware running without audio output
+            log::error!("audio sink init error: {:?}", e);
+            Box::new(sink::NullSink::default())
+        }
+    };
+
+    let mut audio = audio::Audio::new(audio_sink, job::is_stopped);
     spawn(move || {
         audio.play_with_tx(rx2);
     });
     utils::log_heap();
 
+    // init microphone, recording and self test are disabled when it fails
+    log::info!("init mic");
+    match mic::Mic::new(
+        peripherals.i2s0,
+        peripherals.pins.gpio6.into(),
+        peripherals.pins.gpio5.into(),
+        peripherals.pins.gpio4.into(),
+        global::MIC_FORMAT,
+    ) {
+        Ok(mut mic) => {
+            spawn(move || {
+                mic.run();
+            });
+            // wake word detection on the microphone input
+            if let Err(e) = wakeword::start(tx3.clone()) {
+                log::error!("wake word init error: {:?}", e);
+            }
+        }
+        Err(e) => log::error!("mic init error: {:?}", e),
+    }
+    utils::log_heap();
+
     // init tts
     log::info!("init tts");
     let mut tts = tts::TTS::new();
     spawn(move || {
-        tts.play_with_rx(rx, tx2);
+        tts.play_queue(tx2);
     });
     utils::log_heap();
 
@@ -92,9 +174,16 @@ fn main() -> anyhow::Result<()> {
     log::info!("Wifi AP IP: {:?}", wifi_ap.ap_netif().get_ip_info()?);
     utils::log_heap();
 
-    // show hello text
-    _ = tx.clone().send(global::TTS_TEXT_HELLO.to_string());
+    // self-signed https certificate on first boot, needs wifi for true random numbers
+    if let Err(e) = tls::init() {
+        log::error!("tls init error: {:?}", e);
+    }
+
     // speak hello
+    if let Err(e) = job::enqueue(job::Job::speak(global::TTS_TEXT_HELLO.to_string())) {
+        log::warn!("enqueue hello error: {:?}", e);
+    }
+    // show hello text
     _ = tx3.clone().send(global::TTS_TEXT_HELLO.to_string());
 
     // wait k0 button press
@@ -104,9 +193,17 @@ fn main() -> anyhow::Result<()> {
 
     // start server
     log::info!("start server");
-    server::server(tx, tx3)?;
+    server::server(tx3)?;
     utils::log_heap();
 
+    // k0 button plays clip after server started
+    spawn(move || loop {
+        log::info!("wait_for_any_edge btn_k0");
+        let e = btn_k0.wait_for_any_edge();
+        action::Action::PlayClip(global::BUTTON_K0_CLIP.to_string()).run();
+        log::info!("wait_for_any_edge {:?}", e);
+    });
+
     // run ui
     log::info!("ui run");
     ui.run(rx3);
@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread::sleep;
+use std::time::Duration;
+
+use anyhow::bail;
+use esp_idf_svc::hal::{
+    gpio::AnyIOPin,
+    i2s::{config, I2s, I2sDriver, I2sRx},
+    peripheral::Peripheral,
+};
+use serde::Serialize;
+
+use crate::global;
+use crate::wav;
+
+// 麦克风数据格式
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MicFormat {
+    pub sample_rate: u32,
+    // I2S 每个采样的位宽，16 或 32（INMP441 等 24 位麦克风使用 32）
+    pub bits: u8,
+    // 使用右声道（L/R 引脚接高电平）
+    pub right_channel: bool,
+    // 转换为 16 位时额外放大的位数
+    pub gain_shift: u8,
+}
+
+static AVAILABLE: AtomicBool = AtomicBool::new(false);
+static SUBSCRIBERS: Mutex<Vec<mpsc::SyncSender<Vec<i16>>>> = Mutex::new(Vec::new());
+
+pub struct Mic {
+    rx_driver: I2sDriver<'static, I2sRx>,
+    format: MicFormat,
+    resampler: wav::Resampler,
+    buf: Vec<u8>,
+}
+
+impl Mic {
+    pub fn new(
+        i2s: impl Peripheral<P = impl I2s> + 'static,
+        din: AnyIOPin,
+        bclk: AnyIOPin,
+        ws: AnyIOPin,
+        format: MicFormat,
+    ) -> anyhow::Result<Self> {
+        let bits = match format.bits {
+            16 => config::DataBitWidth::Bits16,
+            32 => config::DataBitWidth::Bits32,
+            _ => bail!("unsupported mic bits: {}", format.bits),
+        };
+        let slot_mask = if format.right_channel {
+            config::StdSlotMask::Right
+        } else {
+            config::StdSlotMask::Left
+        };
+        let i2s_config = config::StdConfig::new(
+            config::Config::default(),
+            config::StdClkConfig::from_sample_rate_hz(format.sample_rate),
+            config::StdSlotConfig::philips_slot_default(bits, config::SlotMode::Mono)
+                .slot_mask(slot_mask),
+            config::StdGpioConfig::default(),
+        );
+
+        let mut rx_driver =
+            I2sDriver::new_std_rx(i2s, &i2s_config, bclk, din, Option::<AnyIOPin>::None, ws)?;
+        rx_driver.rx_enable()?;
+        log::info!("I2S mic initialized: {:?}", format);
+
+        Ok(Mic {
+            rx_driver,
+            format,
+            resampler: wav::Resampler::new(format.sample_rate, global::SAMPLE_RATE),
+            buf: vec![0; global::MIC_FRAME_SAMPLES * (format.bits as usize / 8)],
+        })
+    }
+
+    // 读取一帧并转换为 SAMPLE_RATE 采样率的 i16
+    fn read_frame(&mut self) -> anyhow::Result<Vec<i16>> {
+        let n = self.rx_driver.read(&mut self.buf, 1000)?;
+        let data = &self.buf[..n];
+
+        let pcm: Vec<i16> = match self.format.bits {
+            16 => data
+                .chunks_exact(2)
+                .map(|b| {
+                    let s = (i16::from_le_bytes([b[0], b[1]]) as i32) << self.format.gain_shift;
+                    s.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+                })
+                .collect(),
+            _ => data
+                .chunks_exact(4)
+                .map(|b| {
+                    // 24 位数据左对齐在 32 位中
+                    let s = i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as i64;
+                    let s = s >> (16 - self.format.gain_shift.min(16) as i64);
+                    s.clamp(i16::MIN as i64, i16::MAX as i64) as i16
+                })
+                .collect(),
+        };
+
+        Ok(self.resampler.process(&pcm))
+    }
+
+    // 持续采集并分发给所有订阅者
+    pub fn run(&mut self) {
+        AVAILABLE.store(true, Ordering::Relaxed);
+        loop {
+            match self.read_frame() {
+                Ok(frame) => publish(frame),
+                Err(e) => {
+                    log::warn!("mic read error: {:?}", e);
+                    sleep(Duration::from_millis(100));
+                }
+            }
+        }
+    }
+}
+
+fn publish(frame: Vec<i16>) {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|tx| match tx.try_send(frame.clone()) {
+            // 订阅者处理不过来时丢帧，不阻塞采集线程
+            Ok(()) | Err(mpsc::TrySendError::Full(_)) => true,
+            Err(mpsc::TrySendError::Disconnected(_)) => false,
+        });
+}
+
+pub fn is_available() -> bool {
+    AVAILABLE.load(Ordering::Relaxed)
+}
+
+// 订阅麦克风数据，返回的 Receiver 被 drop 后自动取消订阅
+pub fn subscribe() -> mpsc::Receiver<Vec<i16>> {
+    let (tx, rx) = mpsc::sync_channel(global::MIC_QUEUE_LEN);
+    SUBSCRIBERS.lock().unwrap().push(tx);
+    rx
+}
+
+// 录制指定采样数的音频
+pub fn record(samples: usize) -> anyhow::Result<Vec<i16>> {
+    collect(&subscribe(), samples)
+}
+
+// 从已订阅的 Receiver 中读取指定采样数的音频
+pub fn collect(rx: &mpsc::Receiver<Vec<i16>>, samples: usize) -> anyhow::Result<Vec<i16>> {
+    if !is_available() {
+        bail!("microphone not available");
+    }
+
+    let mut pcm = Vec::with_capacity(samples);
+    while pcm.len() < samples {
+        let frame = rx.recv_timeout(Duration::from_secs(1))?;
+        pcm.extend_from_slice(&frame);
+    }
+    pcm.truncate(samples);
+
+    Ok(pcm)
+}
@@ -0,0 +1,330 @@
+// MQTT 客户端：订阅命令主题播报，发布设备状态与事件
+// 设置保存在 NVS 中，修改后重启生效，主题与消息格式见 broker.rs
+//
+// broker 断开后按 MQTT_RECONNECT_MS 自动重连，连接后重新订阅并发布 online
+// 启用 discovery 时连接后及 Home Assistant 重新上线时发布实体配置，见 discovery.rs
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::mqtt::client::{
+    Details, EspMqttClient, EventPayload, LwtConfiguration, MqttClientConfiguration, QoS,
+};
+
+use crate::api::{Device, Limits};
+use crate::audio;
+use crate::auth::Scope;
+use crate::broker::{self, Info, Settings, State, Topics};
+use crate::discovery::{self, Telemetry};
+use crate::event::{self, Event};
+use crate::global;
+use crate::job;
+use crate::nvs;
+use crate::protocol;
+use crate::utils;
+use crate::wifi;
+
+static CONNECTED: AtomicBool = AtomicBool::new(false);
+
+// mqtt 线程处理的消息，客户端只在该线程中使用
+enum Message {
+    Connected,
+    Disconnected,
+    Received { topic: String, data: Vec<u8> },
+    Event(Event),
+}
+
+// mqtt 线程的状态
+struct Session<D> {
+    client: EspMqttClient<'static>,
+    device: D,
+    limits: Limits,
+    settings: Settings,
+    device_id: String,
+    topics: Topics,
+    qos: QoS,
+}
+
+pub fn settings() -> Settings {
+    read()
+        .unwrap_or_else(|e| {
+            log::warn!("read mqtt settings error: {:?}", e);
+            None
+        })
+        .unwrap_or_default()
+}
+
+pub fn info() -> Info {
+    Info::new(&settings(), CONNECTED.load(Ordering::Relaxed))
+}
+
+pub fn set_settings(mut settings: Settings) -> anyhow::Result<()> {
+    settings.validate()?;
+    settings.merge_password(&self::settings());
+    nvs::write(
+        global::MQTT_NVS_NAMESPACE,
+        global::MQTT_SETTINGS_KEY,
+        &serde_json::to_vec(&settings)?,
+    )
+}
+
+// 未启用时不连接，命令与 WebSocket 消息一样通过 device 执行，按 speak 权限处理
+pub fn start(device: impl Device + Send + 'static, limits: Limits) -> anyhow::Result<()> {
+    let settings = settings();
+    if !settings.enabled {
+        log::info!("mqtt disabled");
+        return Ok(());
+    }
+
+    let device_id = utils::device_id();
+    let client_id = settings.client_id(&device_id);
+    let topics = settings.topics(&device_id);
+    let qos = qos(settings.qos);
+
+    let conf = MqttClientConfiguration {
+        client_id: Some(&client_id),
+        username: Some(settings.username.as_str()).filter(|u| !u.is_empty()),
+        password: settings.password.as_deref().filter(|p| !p.is_empty()),
+        keep_alive_interval: Some(Duration::from_secs(settings.keep_alive_secs.into())),
+        reconnect_timeout: Some(Duration::from_millis(global::MQTT_RECONNECT_MS)),
+        buffer_size: global::MQTT_BUFFER_SIZE,
+        // 异常断开时由 broker 发布 offline
+        lwt: Some(LwtConfiguration {
+            topic: &topics.availability,
+            payload: broker::OFFLINE.as_bytes(),
+            qos,
+            retain: true,
+        }),
+        ..Default::default()
+    };
+    let (client, mut connection) = EspMqttClient::new(&settings.url, &conf)?;
+    log::info!("mqtt client {} connecting to {}", client_id, settings.url);
+
+    let (tx, rx) = mpsc::channel();
+
+    // 连接事件在单独的线程中接收，收到后立即交给 mqtt 线程，不在此调用客户端，避免阻塞 MQTT 任务
+    let connection_tx = tx.clone();
+    thread::Builder::new()
+        .name("mqtt_conn".to_string())
+        .stack_size(global::MQTT_CONN_STACK_SIZE)
+        .spawn(move || {
+            while let Ok(event) = connection.next() {
+                let message = match event.payload() {
+                    EventPayload::Connected(_) => Message::Connected,
+                    EventPayload::Disconnected => Message::Disconnected,
+                    EventPayload::Received {
+                        topic,
+                        data,
+                        details,
+                        ..
+                    } => {
+                        // 超过 MQTT_BUFFER_SIZE 的消息会被分段
+                        if !matches!(details, Details::Complete) {
+                            log::warn!("mqtt message exceeds {} bytes", global::MQTT_BUFFER_SIZE);
+                            continue;
+                        }
+                        Message::Received {
+                            topic: topic.unwrap_or_default().to_string(),
+                            data: data.to_vec(),
+                        }
+                    }
+                    EventPayload::Error(e) => {
+                        log::warn!("mqtt error: {:?}", e);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                if connection_tx.send(message).is_err() {
+                    break;
+                }
+            }
+            log::warn!("mqtt connection closed");
+        })?;
+
+    let events = event::subscribe();
+    thread::Builder::new()
+        .name("mqtt_events".to_string())
+        .stack_size(global::MQTT_CONN_STACK_SIZE)
+        .spawn(move || {
+            for event in events {
+                if tx.send(Message::Event(event)).is_err() {
+                    break;
+                }
+            }
+        })?;
+
+    let mut session = Session {
+        client,
+        device,
+        limits,
+        settings,
+        device_id,
+        topics,
+        qos,
+    };
+    thread::Builder::new()
+        .name("mqtt".to_string())
+        .stack_size(global::STACK_SIZE)
+        .spawn(move || session.run(rx))?;
+    Ok(())
+}
+
+impl<D: Device> Session<D> {
+    fn run(&mut self, rx: mpsc::Receiver<Message>) {
+        let interval = Duration::from_millis(global::MQTT_TELEMETRY_MS);
+        let mut last_telemetry = Instant::now();
+        loop {
+            match rx.recv_timeout(interval.saturating_sub(last_telemetry.elapsed())) {
+                Ok(message) => self.handle(message),
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if last_telemetry.elapsed() >= interval {
+                last_telemetry = Instant::now();
+                if CONNECTED.load(Ordering::Relaxed) {
+                    self.publish_telemetry();
+                }
+            }
+        }
+    }
+
+    fn handle(&mut self, message: Message) {
+        match message {
+            Message::Connected => {
+                log::info!("mqtt connected");
+                CONNECTED.store(true, Ordering::Relaxed);
+                // 未保留会话时 broker 不保存订阅，每次连接都重新订阅
+                let mut subscriptions = vec![self.topics.command.clone()];
+                if self.settings.discovery {
+                    subscriptions.push(discovery::status_topic(&self.settings.discovery_prefix));
+                }
+                for topic in subscriptions {
+                    if let Err(e) = self.client.subscribe(&topic, self.qos) {
+                        log::error!("mqtt subscribe {} error: {:?}", topic, e);
+                    }
+                }
+                let availability = self.topics.availability.clone();
+                self.publish(&availability, true, broker::ONLINE);
+                self.publish_discovery();
+                self.publish_state();
+                self.publish_telemetry();
+            }
+            Message::Disconnected => {
+                log::warn!("mqtt disconnected");
+                CONNECTED.store(false, Ordering::Relaxed);
+            }
+            Message::Received { topic, data } if topic == self.topics.command => {
+                // 能向命令主题发布消息即视为有 speak 权限，访问控制由 broker 的账号与 ACL 负责
+                let mut auth = protocol::Session {
+                    authorization: None,
+                    scope: Some(Scope::Speak),
+                };
+                let reply = protocol::handle(
+                    &mut self.device,
+                    &self.limits,
+                    &mut auth,
+                    &broker::command(&data),
+                );
+                let event_topic = self.topics.event.clone();
+                self.publish(&event_topic, false, &reply);
+            }
+            // Home Assistant 重启后 retain 的配置可能丢失
+            Message::Received { data, .. } => {
+                if data == broker::ONLINE.as_bytes() {
+                    self.publish_discovery();
+                    self.publish_state();
+                    self.publish_telemetry();
+                }
+            }
+            Message::Event(event) => {
+                if !CONNECTED.load(Ordering::Relaxed) {
+                    return;
+                }
+                let event_topic = self.topics.event.clone();
+                self.publish(&event_topic, false, &protocol::encode_event(&event));
+                if matches!(
+                    event,
+                    Event::Queued { .. }
+                        | Event::Started { .. }
+                        | Event::Finished { .. }
+                        | Event::Stopped { .. }
+                        | Event::Volume { .. }
+                ) {
+                    self.publish_state();
+                }
+            }
+        }
+    }
+
+    fn publish_discovery(&mut self) {
+        if !self.settings.discovery {
+            return;
+        }
+        let device = discovery::Device {
+            id: &self.device_id,
+            name: global::WIFI_AP_NAME,
+            model: global::DEVICE_MODEL,
+            version: env!("CARGO_PKG_VERSION"),
+        };
+        let configs = discovery::configs(&self.settings.discovery_prefix, &device, &self.topics);
+        for (topic, config) in configs {
+            self.publish(&topic, true, &config);
+        }
+    }
+
+    fn publish_state(&mut self) {
+        let state = State {
+            volume: audio::volume(),
+            speaking: audio::is_speaking(),
+            queue_len: job::pending_len(),
+        };
+        let topic = self.topics.state.clone();
+        self.publish(
+            &topic,
+            true,
+            &serde_json::to_string(&state).unwrap_or_default(),
+        );
+    }
+
+    fn publish_telemetry(&mut self) {
+        let heap = utils::heap();
+        let telemetry = Telemetry {
+            heap_free: heap.internal_free,
+            psram_free: heap.psram_free,
+            uptime_secs: utils::uptime_secs(),
+            rssi: wifi::rssi(),
+        };
+        let topic = discovery::telemetry_topic(&self.topics);
+        self.publish(
+            &topic,
+            false,
+            &serde_json::to_string(&telemetry).unwrap_or_default(),
+        );
+    }
+
+    fn publish(&mut self, topic: &str, retain: bool, payload: &str) {
+        if let Err(e) = self
+            .client
+            .publish(topic, self.qos, retain, payload.as_bytes())
+        {
+            log::warn!("mqtt publish {} error: {:?}", topic, e);
+        }
+    }
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+fn read() -> anyhow::Result<Option<Settings>> {
+    match nvs::read(global::MQTT_NVS_NAMESPACE, global::MQTT_SETTINGS_KEY)? {
+        Some(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+        None => Ok(None),
+    }
+}
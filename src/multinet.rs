@@ -0,0 +1,102 @@
+use std::ffi::CString;
+
+use anyhow::bail;
+use esp_idf_svc::sys::esp_sr;
+
+use crate::command::Command;
+
+// 单次检测的结果
+pub enum Detect {
+    Detecting,
+    // 命中命令表中的序号
+    Command(usize),
+    Timeout,
+}
+
+// esp-sr MultiNet 离线命令词识别
+pub struct MultiNet {
+    iface: *mut esp_sr::esp_mn_iface_t,
+    data: *mut esp_sr::model_iface_data_t,
+}
+
+unsafe impl Send for MultiNet {}
+
+impl MultiNet {
+    // duration_ms: 唤醒后等待命令的时长
+    pub fn new(models: *mut esp_sr::srmodel_list_t, duration_ms: i32) -> anyhow::Result<Self> {
+        unsafe {
+            let name = esp_sr::esp_srmodel_filter(
+                models,
+                esp_sr::ESP_MN_PREFIX.as_ptr() as *const _,
+                esp_sr::ESP_MN_CHINESE.as_ptr() as *const _,
+            );
+            if name.is_null() {
+                bail!("multinet model not found");
+            }
+
+            let iface = esp_sr::esp_mn_handle_from_name(name);
+            if iface.is_null() {
+                bail!("multinet handle not found");
+            }
+            let data = ((*iface).create.unwrap())(name, duration_ms);
+            if data.is_null() {
+                bail!("multinet create failed");
+            }
+            let ret = esp_sr::esp_mn_commands_alloc(iface, data);
+            if ret != esp_sr::ESP_OK {
+                bail!("esp_mn_commands_alloc failed: {}", ret);
+            }
+            log::info!("esp multinet initialized");
+
+            Ok(MultiNet { iface, data })
+        }
+    }
+
+    // 命令 ID 为命令表序号 + 1
+    pub fn set_commands(&mut self, commands: &[Command]) -> anyhow::Result<()> {
+        unsafe {
+            esp_sr::esp_mn_commands_clear();
+            for (i, command) in commands.iter().enumerate() {
+                let phrase = CString::new(command.phrase.as_str())?;
+                let ret = esp_sr::esp_mn_commands_add(i as i32 + 1, phrase.as_ptr());
+                if ret != esp_sr::ESP_OK {
+                    bail!("esp_mn_commands_add {:?} failed: {}", command.phrase, ret);
+                }
+            }
+            if !esp_sr::esp_mn_commands_update().is_null() {
+                bail!("esp_mn_commands_update failed");
+            }
+        }
+        log::info!("multinet commands: {}", commands.len());
+        Ok(())
+    }
+
+    pub fn chunksize(&self) -> usize {
+        unsafe { ((*self.iface).get_samp_chunksize.unwrap())(self.data) as usize }
+    }
+
+    // pcm 长度必须为 chunksize
+    pub fn detect(&mut self, pcm: &[i16]) -> Detect {
+        unsafe {
+            let state = ((*self.iface).detect.unwrap())(self.data, pcm.as_ptr() as *mut i16);
+            match state {
+                esp_sr::esp_mn_state_t_ESP_MN_STATE_DETECTED => {
+                    let res = ((*self.iface).get_results.unwrap())(self.data);
+                    if res.is_null() || (*res).num < 1 || (*res).command_id[0] < 1 {
+                        return Detect::Detecting;
+                    }
+                    Detect::Command((*res).command_id[0] as usize - 1)
+                }
+                esp_sr::esp_mn_state_t_ESP_MN_STATE_TIMEOUT => Detect::Timeout,
+                _ => Detect::Detecting,
+            }
+        }
+    }
+
+    // 开始新一轮识别前清除状态
+    pub fn clean(&mut self) {
+        unsafe {
+            ((*self.iface).clean.unwrap())(self.data);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// NVS 键值存储，保存需要在重启后保留的小块数据，例如认证凭据
+
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use esp_idf_svc::nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault};
+
+static PARTITION: OnceLock<EspDefaultNvsPartition> = OnceLock::new();
+
+pub fn init(partition: EspDefaultNvsPartition) {
+    _ = PARTITION.set(partition);
+}
+
+fn open(namespace: &str) -> anyhow::Result<EspNvs<NvsDefault>> {
+    let partition = PARTITION
+        .get()
+        .ok_or_else(|| anyhow!("nvs not initialized"))?
+        .clone();
+    Ok(EspNvs::new(partition, namespace, true)?)
+}
+
+// 不存在时返回 None
+pub fn read(namespace: &str, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let nvs = open(namespace)?;
+    let Some(len) = nvs.blob_len(key)? else {
+        return Ok(None);
+    };
+    let mut buf = vec![0; len];
+    Ok(nvs.get_blob(key, &mut buf)?.map(|data| data.to_vec()))
+}
+
+pub fn write(namespace: &str, key: &str, data: &[u8]) -> anyhow::Result<()> {
+    open(namespace)?.set_blob(key, data)?;
+    Ok(())
+}
+
+// 不存在时返回 false
+pub fn remove(namespace: &str, key: &str) -> anyhow::Result<bool> {
+    Ok(open(namespace)?.remove(key)?)
+}
@@ -1,19 +1,61 @@
+use std::io::Cursor;
 use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 use embedded_svc::{
-    http::{Headers, Method},
+    http::{server::Request, Headers, Method},
     io::{Read, Write},
 };
-use esp_idf_svc::http::server::EspHttpServer;
+use esp_idf_svc::http::server::{
+    ws::EspHttpWsConnection, Configuration, EspHttpConnection, EspHttpServer,
+};
+use esp_idf_svc::tls::X509;
+use esp_idf_svc::ws::FrameType;
 
 use serde::{Deserialize, Serialize};
 
+use anyhow::anyhow;
+
+use crate::access;
+use crate::api::{self, ApiError};
 use crate::audio;
+use crate::auth::{self, Denied, Scope};
+use crate::broker;
+use crate::cert::Identity;
+use crate::clip;
+use crate::clock;
+use crate::command::{self, Command};
+use crate::decoder;
+use crate::earcon::{self, Earcon, Earcons};
+use crate::effect;
 use crate::global;
+use crate::job;
+use crate::line;
+use crate::loopback;
+use crate::loudness;
+use crate::mic;
+use crate::mixer;
+use crate::mqtt;
+use crate::protocol;
+use crate::socket;
+use crate::status;
+use crate::stream;
+use crate::telemetry::Telemetry;
+use crate::tls;
+use crate::tone;
+use crate::trim;
+use crate::wav;
+use crate::wifi;
+use crate::ws;
 
 #[derive(Debug, Deserialize)]
 struct TTSRequest {
-    text: String, // 文本内容
+    text: String,         // 文本内容
+    pre: Option<Earcon>,  // 播报前的提示音，例如 "tone:ding_dong"
+    post: Option<Earcon>, // 播报后的提示音
+    #[serde(default)]
+    priority: u8, // 数值越大越先播放
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,7 +63,63 @@ struct VolumeRequest {
     op: String, // 操作类型: "inc" 或 "dec"
 }
 
-pub fn server(ui_tx: mpsc::Sender<String>, tts_tx: mpsc::Sender<String>) -> anyhow::Result<()> {
+#[derive(Debug, Deserialize)]
+struct MixerRequest {
+    duck_gain: f32, // 语音播报时背景音源的增益 0.0 ~ 1.0
+}
+
+#[derive(Debug, Deserialize)]
+struct SourceRequest {
+    clip: String, // 音频库中的音频名称
+    #[serde(default = "default_gain")]
+    gain: f32,
+    #[serde(default = "default_priority")]
+    priority: u8,
+    #[serde(default, rename = "loop")]
+    looping: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RecordRequest {
+    name: String, // 保存到音频库的名称
+    seconds: u32, // 录音时长
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeRequest {
+    epoch: u64, // UTC 秒
+}
+
+#[derive(Debug, Deserialize)]
+struct GainRequest {
+    gain: f32,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+fn default_priority() -> u8 {
+    global::MIXER_DEFAULT_PRIORITY
+}
+
+#[derive(Debug, Deserialize)]
+struct QueueRequest {
+    max_len: usize, // 队列长度上限
+}
+
+#[derive(Debug, Serialize)]
+struct IdResponse {
+    id: u32, // 播报 ID
+}
+
+#[derive(Debug, Serialize)]
+struct QueueResponse {
+    max_len: usize,
+    items: Vec<job::Pending>, // 按播放顺序
+}
+
+pub fn server(ui_tx: mpsc::Sender<String>) -> anyhow::Result<()> {
     log::info!("starting server");
 
     let mut server = create_server()?;
@@ -32,7 +130,11 @@ pub fn server(ui_tx: mpsc::Sender<String>, tts_tx: mpsc::Sender<String>) -> anyh
             .map(|_| ())
     })?;
 
-    _ = server.fn_handler::<anyhow::Error, _>("/api/tts", Method::Post, move |mut req| {
+    let ui = ui_tx.clone();
+    _ = server.fn_handler::<anyhow::Error, _>("/api/tts", Method::Post, move |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
         let len = req.content_len().unwrap_or(0) as usize;
         if len > global::MAX_LEN {
             req.into_status_response(413)?
@@ -42,22 +144,105 @@ pub fn server(ui_tx: mpsc::Sender<String>, tts_tx: mpsc::Sender<String>) -> anyh
 
         let mut buf = vec![0; len];
         req.read_exact(&mut buf)?;
-        let mut resp = req.into_ok_response()?;
 
         if let Ok(request) = serde_json::from_slice::<TTSRequest>(&buf) {
             log::info!("request: {:?}", request);
-            _ = ui_tx.send(request.text.clone());
-            _ = tts_tx.send(request.text);
+            // 未指定的提示音使用设备默认值
+            let defaults = earcon::defaults();
+            let earcons = Earcons {
+                pre: request.pre.unwrap_or(defaults.pre),
+                post: request.post.unwrap_or(defaults.post),
+            };
+            if let Err(e) = earcons.validate() {
+                req.into_status_response(400)?
+                    .write_all(e.to_string().as_bytes())?;
+                return Ok(());
+            }
+            if let Err(e) = api::check_text(&request.text) {
+                req.into_status_response(400)?
+                    .write_all(e.message.as_bytes())?;
+                return Ok(());
+            }
+
+            _ = ui.send(request.text.clone());
+            let result = job::enqueue_with_priority(
+                job::Job::announce(request.text, earcons),
+                request.priority,
+            );
+            respond_id(req, result)?;
         } else {
-            resp.write_all("JSON error".as_bytes())?;
-            return Ok(());
+            req.into_ok_response()?.write_all("JSON error".as_bytes())?;
         }
 
-        resp.write_all("{}".as_bytes())?;
         Ok(())
     });
 
-    _ = server.fn_handler::<anyhow::Error, _>("/api/volume", Method::Put, |mut req| {
+    _ = server.fn_handler::<anyhow::Error, _>("/api/play", Method::Post, move |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        // 播放音频库中的片段：/api/play?clip=名称
+        if let Some(name) = query_param(req.uri(), "clip") {
+            if !clip::exists(name) {
+                req.into_status_response(404)?
+                    .write_all("Clip not found".as_bytes())?;
+                return Ok(());
+            }
+            return respond_id(req, job::enqueue(job::Job::clip(name.to_string())));
+        }
+
+        let len = req.content_len().unwrap_or(0) as usize;
+        if len > global::MAX_PLAY_LEN {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        }
+
+        let mut buf = vec![0; len];
+        req.read_exact(&mut buf)?;
+
+        // WAV / MP3 按文件头解码，文件头无效时返回 400，否则按 query 参数描述的裸 PCM 处理
+        let pcm = if wav::is_wav(&buf) || decoder::is_mp3(&buf) {
+            decoder::Stream::open(Cursor::new(buf), global::SAMPLE_RATE)
+                .and_then(|stream| stream.decode_all())
+        } else {
+            raw_pcm_format(req.uri())
+                .map(|format| wav::to_output(&format, &buf, global::SAMPLE_RATE))
+        };
+
+        match pcm {
+            Ok(pcm) => {
+                log::info!("play {} bytes -> {} samples", len, pcm.len());
+                respond_id(req, job::enqueue(job::Job::play(pcm)))?;
+            }
+            Err(e) => {
+                log::warn!("play error: {:?}", e);
+                req.into_status_response(400)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/stop", Method::Post, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        // ?all=1 同时清空队列
+        if query_param(req.uri(), "all") == Some("1") {
+            job::stop_all();
+        } else {
+            job::stop();
+        }
+        req.into_ok_response()?.write_all("{}".as_bytes())?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/volume", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
         let len = req.content_len().unwrap_or(0) as usize;
 
         if len > global::MAX_LEN {
@@ -92,16 +277,1215 @@ pub fn server(ui_tx: mpsc::Sender<String>, tts_tx: mpsc::Sender<String>) -> anyh
         Ok(())
     })?;
 
+    _ = server.fn_handler::<anyhow::Error, _>("/api/queue", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&queue_response())?)?;
+        Ok(())
+    })?;
+
+    // 修改队列长度上限 {"max_len": 8}，已在队列中的播报不受影响
+    _ = server.fn_handler::<anyhow::Error, _>("/api/queue", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(request) = serde_json::from_slice::<QueueRequest>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", request);
+
+        if !(1..=global::QUEUE_MAX_LEN_LIMIT).contains(&request.max_len) {
+            req.into_status_response(400)?
+                .write_all("Invalid max_len".as_bytes())?;
+            return Ok(());
+        }
+        job::set_max_len(request.max_len);
+        req.into_ok_response()?.write_all("{}".as_bytes())?;
+        Ok(())
+    })?;
+
+    // 清空等待中的播报，正在播放的不受影响
+    _ = server.fn_handler::<anyhow::Error, _>("/api/queue", Method::Delete, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let removed = job::flush();
+        log::info!("flush queue: {} removed", removed);
+        req.into_ok_response()?
+            .write_all(format!("{{\"removed\": {}}}", removed).as_bytes())?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/queue/*", Method::Delete, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let Some(id) = queue_id(req.uri()) else {
+            req.into_status_response(400)?
+                .write_all("Invalid id".as_bytes())?;
+            return Ok(());
+        };
+        if job::cancel(id) {
+            req.into_ok_response()?.write_all("{}".as_bytes())?;
+        } else {
+            req.into_status_response(404)?
+                .write_all("Not pending".as_bytes())?;
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/clips", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        match clip::library() {
+            Ok(library) => {
+                req.into_ok_response()?
+                    .write_all(&serde_json::to_vec(&library)?)?;
+            }
+            Err(e) => {
+                req.into_status_response(500)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/clips/*", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let name = clip_name(req.uri()).to_string();
+        let len = req.content_len().unwrap_or(0) as usize;
+
+        match clip::save(&name, len, &mut req) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                log::warn!("save clip {} error: {:?}", name, e);
+                req.into_status_response(400)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/clips/*", Method::Delete, |req| {
+        let Some(req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let name = clip_name(req.uri()).to_string();
+
+        if !clip::exists(&name) {
+            req.into_status_response(404)?
+                .write_all("Clip not found".as_bytes())?;
+            return Ok(());
+        }
+        match clip::delete(&name) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                req.into_status_response(500)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/mixer", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let info = audio::mixer().info();
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&info)?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/mixer", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        if let Ok(request) = serde_json::from_slice::<MixerRequest>(&buf) {
+            log::info!("request: {:?}", request);
+            audio::mixer().set_duck_gain(request.duck_gain);
+            req.into_ok_response()?.write_all("{}".as_bytes())?;
+        } else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/mixer/sources", Method::Post, |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(request) = serde_json::from_slice::<SourceRequest>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", request);
+
+        if request.priority >= mixer::SPEECH_PRIORITY {
+            req.into_status_response(400)?
+                .write_all("Priority reserved for speech".as_bytes())?;
+            return Ok(());
+        }
+        if !clip::exists(&request.clip) {
+            req.into_status_response(404)?
+                .write_all("Clip not found".as_bytes())?;
+            return Ok(());
+        }
+
+        // 播放时逐块解码，循环播放到结尾时重新打开
+        let id = audio::mixer().alloc_id();
+        let name = request.clip.clone();
+        let source = mixer::Source::decoder(
+            id,
+            &request.clip,
+            request.priority,
+            move || clip::open(&name),
+            request.looping,
+        )?
+        .with_gain(request.gain);
+        let added = audio::mixer().add(source, global::MIXER_MAX_SOURCES);
+        if let Err(e) = added {
+            req.into_status_response(503)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&IdResponse { id })?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/mixer/sources/*", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let Some(id) = source_id(req.uri()) else {
+            req.into_status_response(400)?
+                .write_all("Invalid source id".as_bytes())?;
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(request) = serde_json::from_slice::<GainRequest>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+
+        if audio::mixer().set_gain(id, request.gain) {
+            req.into_ok_response()?.write_all("{}".as_bytes())?;
+        } else {
+            req.into_status_response(404)?
+                .write_all("Source not found".as_bytes())?;
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/mixer/sources/*", Method::Delete, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        // 语音音源不可删除
+        let id = source_id(req.uri()).filter(|id| *id != mixer::SPEECH_ID);
+        if id.is_some_and(|id| audio::mixer().remove(id)) {
+            req.into_ok_response()?.write_all("{}".as_bytes())?;
+        } else {
+            req.into_status_response(404)?
+                .write_all("Source not found".as_bytes())?;
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/clips/*", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let name = clip_name(req.uri()).to_string();
+
+        let Ok((mut file, content_type)) = clip::file(&name) else {
+            req.into_status_response(404)?
+                .write_all("Clip not found".as_bytes())?;
+            return Ok(());
+        };
+
+        let mut resp = req.into_response(200, None, &[("Content-Type", content_type)])?;
+        let mut buf = vec![0u8; 4096];
+        loop {
+            let n = std::io::Read::read(&mut file, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            resp.write_all(&buf[..n])?;
+        }
+        Ok(())
+    })?;
+
+    // 录音期间阻塞当前请求，最长 MAX_RECORD_SECONDS
+    _ = server.fn_handler::<anyhow::Error, _>("/api/record", Method::Post, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(request) = serde_json::from_slice::<RecordRequest>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", request);
+
+        if request.seconds == 0
+            || request.seconds > global::MAX_RECORD_SECONDS
+            || clip::validate_name(&request.name).is_err()
+        {
+            req.into_status_response(400)?
+                .write_all("Invalid request".as_bytes())?;
+            return Ok(());
+        }
+        if !mic::is_available() {
+            req.into_status_response(503)?
+                .write_all("Microphone not available".as_bytes())?;
+            return Ok(());
+        }
+
+        let samples = (global::SAMPLE_RATE * request.seconds) as usize;
+        match mic::record(samples).and_then(|pcm| clip::save_pcm(&request.name, &pcm)) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                log::warn!("record error: {:?}", e);
+                req.into_status_response(500)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    // 扬声器 / 麦克风回环自检
+    _ = server.fn_handler::<anyhow::Error, _>(
+        "/api/selftest/loopback",
+        Method::Post,
+        move |req| {
+            let Some(req) = authorize(req, Scope::Admin)? else {
+                return Ok(());
+            };
+            if !mic::is_available() {
+                req.into_status_response(503)?
+                    .write_all("Microphone not available".as_bytes())?;
+                return Ok(());
+            }
+
+            // 只停止当前的播报，测试音插到队列最前
+            job::stop();
+            let rx = mic::subscribe();
+            let tone = tone::sine(
+                global::LOOPBACK_TONE_HZ,
+                global::LOOPBACK_TONE_MS,
+                global::SAMPLE_RATE,
+                0.5,
+            );
+            job::enqueue_with_priority(job::Job::play(tone), u8::MAX)?;
+
+            let samples = (global::SAMPLE_RATE * global::LOOPBACK_RECORD_MS / 1000) as usize;
+            let pcm = mic::collect(&rx, samples)?;
+            let report = loopback::analyze(&pcm, global::LOOPBACK_TONE_HZ, global::SAMPLE_RATE);
+            log::info!("loopback: {:?}", report);
+
+            req.into_ok_response()?
+                .write_all(&serde_json::to_vec(&report)?)?;
+            Ok(())
+        },
+    )?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/commands", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&command::list())?)?;
+        Ok(())
+    })?;
+
+    // 替换语音命令表，在下次唤醒前生效
+    _ = server.fn_handler::<anyhow::Error, _>("/api/commands", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_COMMANDS_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(commands) = serde_json::from_slice::<Vec<Command>>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", commands);
+
+        if let Err(e) = command::validate(&commands) {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        match command::save(&commands) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                req.into_status_response(500)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/time", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(request) = serde_json::from_slice::<TimeRequest>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+
+        match clock::set(request.epoch) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                req.into_status_response(400)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    // 生成音调序列并播放，例如 {"tones": [{"type": "dtmf", "digits": "123"}]}
+    // 或播放内置音调：/api/tone?preset=ding_dong
+    _ = server.fn_handler::<anyhow::Error, _>("/api/tone", Method::Post, move |req| {
+        let Some(mut req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        if let Some(name) = query_param(req.uri(), "preset") {
+            let Some(sequence) = tone::preset(name) else {
+                req.into_status_response(404)?
+                    .write_all("Preset not found".as_bytes())?;
+                return Ok(());
+            };
+            let pcm = sequence.render(global::SAMPLE_RATE);
+            return respond_id(req, job::enqueue(job::Job::play(pcm)));
+        }
+
+        let Some(buf) = read_body(&mut req, global::MAX_TONE_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(sequence) = serde_json::from_slice::<tone::Sequence>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", sequence);
+
+        if let Err(e) = sequence.validate(global::SAMPLE_RATE, global::MAX_TONE_MS) {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+
+        let pcm = sequence.render(global::SAMPLE_RATE);
+        respond_id(req, job::enqueue(job::Job::play(pcm)))
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/earcons", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&earcon::defaults())?)?;
+        Ok(())
+    })?;
+
+    // 设备默认的提示音，例如 {"pre": "tone:ding_dong", "post": "none"}
+    _ = server.fn_handler::<anyhow::Error, _>("/api/earcons", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(earcons) = serde_json::from_slice::<Earcons>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", earcons);
+
+        if let Err(e) = earcons.validate() {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        match earcon::save(&earcons) {
+            Ok(()) => {
+                req.into_ok_response()?.write_all("{}".as_bytes())?;
+            }
+            Err(e) => {
+                req.into_status_response(500)?
+                    .write_all(e.to_string().as_bytes())?;
+            }
+        }
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/telemetry", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&audio::stats())?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/status", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&status::collect())?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/loudness", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let settings = *global::LOUDNESS.get().unwrap().lock().unwrap();
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&settings)?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/loudness", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(settings) = serde_json::from_slice::<loudness::Settings>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", settings);
+
+        if let Err(e) = settings.validate() {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        *global::LOUDNESS.get().unwrap().lock().unwrap() = settings;
+        req.into_ok_response()?.write_all("{}".as_bytes())?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/voice", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let settings = *global::VOICE_EFFECT.get().unwrap().lock().unwrap();
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&settings)?)?;
+        Ok(())
+    })?;
+
+    // 设置语速与音调 {"tempo": 1.2, "pitch": 1.0}，或使用内置音色：/api/voice?preset=child
+    _ = server.fn_handler::<anyhow::Error, _>("/api/voice", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let settings = if let Some(name) = query_param(req.uri(), "preset") {
+            let Some(settings) = effect::Settings::preset(name) else {
+                req.into_status_response(404)?
+                    .write_all("Preset not found".as_bytes())?;
+                return Ok(());
+            };
+            settings
+        } else {
+            let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+                req.into_status_response(413)?
+                    .write_all("Request too big".as_bytes())?;
+                return Ok(());
+            };
+            let Ok(settings) = serde_json::from_slice::<effect::Settings>(&buf) else {
+                req.into_status_response(400)?
+                    .write_all("JSON error".as_bytes())?;
+                return Ok(());
+            };
+            settings
+        };
+        log::info!("request: {:?}", settings);
+
+        if let Err(e) = settings.validate() {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        *global::VOICE_EFFECT.get().unwrap().lock().unwrap() = settings;
+        req.into_ok_response()?.write_all("{}".as_bytes())?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/silence", Method::Get, |req| {
+        let Some(req) = authorize(req, Scope::Speak)? else {
+            return Ok(());
+        };
+        let settings = *global::SILENCE.get().unwrap().lock().unwrap();
+        req.into_ok_response()?
+            .write_all(&serde_json::to_vec(&settings)?)?;
+        Ok(())
+    })?;
+
+    _ = server.fn_handler::<anyhow::Error, _>("/api/silence", Method::Put, |req| {
+        let Some(mut req) = authorize(req, Scope::Admin)? else {
+            return Ok(());
+        };
+        let Some(buf) = read_body(&mut req, global::MAX_LEN)? else {
+            req.into_status_response(413)?
+                .write_all("Request too big".as_bytes())?;
+            return Ok(());
+        };
+
+        let Ok(settings) = serde_json::from_slice::<trim::Settings>(&buf) else {
+            req.into_status_response(400)?
+                .write_all("JSON error".as_bytes())?;
+            return Ok(());
+        };
+        log::info!("request: {:?}", settings);
+
+        if let Err(e) = settings.validate() {
+            req.into_status_response(400)?
+                .write_all(e.to_string().as_bytes())?;
+            return Ok(());
+        }
+        *global::SILENCE.get().unwrap().lock().unwrap() = settings;
+        req.into_ok_response()?.write_all("{}".as_bytes())?;
+        Ok(())
+    })?;
+
+    // REST API v1，路由与校验见 api.rs
+    for method in [Method::Get, Method::Post, Method::Put, Method::Delete] {
+        let ui_tx = ui_tx.clone();
+        _ = server.fn_handler::<anyhow::Error, _>("/api/v1/*", method, move |mut req| {
+            let method = match method {
+                Method::Get => api::Method::Get,
+                Method::Post => api::Method::Post,
+                Method::Put => api::Method::Put,
+                _ => api::Method::Delete,
+            };
+            let uri = req.uri().to_string();
+            let authorization = req.header("Authorization").map(str::to_string);
+
+            // 证书上传的请求体较大
+            let max_len = if uri.starts_with("/api/v1/tls/cert") {
+                global::MAX_CERT_LEN
+            } else {
+                global::MAX_API_LEN
+            };
+            let resp = match read_body(&mut req, max_len)? {
+                Some(body) => {
+                    let mut device = ApiDevice {
+                        ui_tx: ui_tx.clone(),
+                    };
+                    let request = api::Request {
+                        method,
+                        uri: &uri,
+                        authorization: authorization.as_deref(),
+                        body: &body,
+                    };
+                    api::handle(&mut device, &API_LIMITS, &request)
+                }
+                None => ApiError::payload_too_large(max_len).to_response(),
+            };
+
+            if resp.status == 401 {
+                req.into_response(
+                    resp.status,
+                    None,
+                    &[
+                        ("Content-Type", "application/json"),
+                        ("WWW-Authenticate", global::AUTH_CHALLENGE),
+                    ],
+                )?
+                .write_all(&resp.body)?;
+            } else if resp.body.is_empty() {
+                req.into_status_response(resp.status)?;
+            } else {
+                req.into_response(resp.status, None, &[("Content-Type", "application/json")])?
+                    .write_all(&resp.body)?;
+            }
+            Ok(())
+        })?;
+    }
+
+    // MQTT 命令与 WebSocket 消息使用相同的处理
+    let device = ApiDevice {
+        ui_tx: ui_tx.clone(),
+    };
+    if let Err(e) = mqtt::start(device.clone(), API_LIMITS) {
+        log::error!("mqtt start error: {:?}", e);
+    }
+    // TCP / UDP 文本协议，见 line.rs
+    if let Err(e) = socket::start(device, API_LIMITS) {
+        log::error!("socket start error: {:?}", e);
+    }
+
+    // WebSocket：接收 speak / stop / volume 命令，推送设备事件，消息格式见 protocol.rs
+    ws::start()?;
+    _ = server.ws_handler(
+        "/ws",
+        move |ws: &mut EspHttpWsConnection| -> anyhow::Result<()> {
+            if ws.is_new() {
+                let mut auth = protocol::Session::default();
+                auth.refresh(access::authenticate);
+                ws::add(ws.session(), ws.create_detached_sender()?, auth);
+                return Ok(());
+            }
+            if ws.is_closed() {
+                ws::remove(ws.session());
+                return Ok(());
+            }
+
+            let (frame_type, len) = ws.recv(&mut [])?;
+            // 未读取的帧内容无法跳过，回复错误后关闭连接
+            if len > global::MAX_API_LEN {
+                let e = ApiError::payload_too_large(global::MAX_API_LEN);
+                ws.send(
+                    FrameType::Text(false),
+                    protocol::encode_error(None, &e).as_bytes(),
+                )?;
+                ws.send(FrameType::Close, &[])?;
+                anyhow::bail!("ws frame of {} bytes exceeds {}", len, global::MAX_API_LEN);
+            }
+            let mut buf = vec![0; len];
+            ws.recv(&mut buf)?;
+            if !matches!(frame_type, FrameType::Text(_)) {
+                return Ok(());
+            }
+
+            let mut device = ApiDevice {
+                ui_tx: ui_tx.clone(),
+            };
+            let revision = access::revision();
+            let mut auth = ws::auth(ws.session());
+            let reply = protocol::handle(&mut device, &API_LIMITS, &mut auth, &buf);
+            ws::set_auth(ws.session(), auth, revision);
+            ws.send(FrameType::Text(false), reply.as_bytes())?;
+            Ok(())
+        },
+    )?;
+
+    // 实时监听扬声器输出：先发送格式 {"sample_rate": 16000, ...}，之后为 PCM 二进制帧
+    // 启用认证时需先发送 {"type": "auth", ...}，认证通过后开始推送
+    _ = server.ws_handler(
+        "/ws/audio",
+        |ws: &mut EspHttpWsConnection| -> anyhow::Result<()> {
+            if ws.is_new() {
+                if access::authorize(None, Scope::Speak).is_ok() {
+                    start_stream(ws, None)?;
+                }
+                return Ok(());
+            }
+            if ws.is_closed() {
+                stream::stop(ws.session());
+                return Ok(());
+            }
+
+            let (frame_type, len) = ws.recv(&mut [])?;
+            if len > global::MAX_LEN {
+                ws.send(FrameType::Close, &[])?;
+                return Ok(());
+            }
+            let mut buf = vec![0; len];
+            ws.recv(&mut buf)?;
+            // 除 auth 外忽略客户端发来的数据
+            if !matches!(frame_type, FrameType::Text(_)) {
+                return Ok(());
+            }
+            let Ok(protocol::Message {
+                command: protocol::Command::Auth(request),
+                ..
+            }) = protocol::decode(&buf)
+            else {
+                return Ok(());
+            };
+            match access::authorize(Some(&request.authorization), Scope::Speak) {
+                Ok(_) => start_stream(ws, Some(request.authorization))?,
+                Err(e) => {
+                    log::warn!("audio stream auth error: {}", e);
+                    let resp = ApiError::from(e).to_response();
+                    ws.send(FrameType::Text(false), &resp.body)?;
+                    ws.send(FrameType::Close, &[])?;
+                }
+            }
+            Ok(())
+        },
+    )?;
+
     core::mem::forget(server);
 
     Ok(())
 }
 
-fn create_server() -> anyhow::Result<EspHttpServer<'static>> {
-    let server_configuration = esp_idf_svc::http::server::Configuration {
+fn start_stream(
+    ws: &mut EspHttpWsConnection,
+    authorization: Option<String>,
+) -> anyhow::Result<()> {
+    if !stream::start(ws.session(), ws.create_detached_sender()?, authorization)? {
+        log::warn!("too many audio stream listeners");
+        ws.send(FrameType::Close, &[])?;
+    }
+    Ok(())
+}
+
+const API_LIMITS: api::Limits = api::Limits {
+    max_text_len: global::MAX_TEXT_LEN,
+    sample_rate: global::SAMPLE_RATE,
+    max_tone_ms: global::MAX_TONE_MS,
+    max_queue_len: global::QUEUE_MAX_LEN_LIMIT,
+    reserved_ports: global::RESERVED_PORTS,
+};
+
+// api::Device 的固件实现
+#[derive(Clone)]
+struct ApiDevice {
+    ui_tx: mpsc::Sender<String>,
+}
+
+impl api::Device for ApiDevice {
+    fn volume(&self) -> u8 {
+        audio::volume()
+    }
+
+    fn set_volume(&mut self, level: u8) {
+        audio::set_volume(level);
+    }
+
+    fn speak(&mut self, request: api::SpeakRequest) -> Result<u32, ApiError> {
+        // 未指定的提示音使用设备默认值
+        let defaults = earcon::defaults();
+        let parse = |value: Option<String>, default: Earcon| match value {
+            Some(value) => {
+                Earcon::try_from(value).map_err(|e| ApiError::bad_request(e.to_string()))
+            }
+            None => Ok(default),
+        };
+        let earcons = Earcons {
+            pre: parse(request.pre, defaults.pre)?,
+            post: parse(request.post, defaults.post)?,
+        };
+        earcons
+            .validate()
+            .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+        _ = self.ui_tx.send(request.text.clone());
+        let job = job::Job::announce(request.text, earcons);
+        Ok(job::enqueue_with_priority(job, request.priority)?)
+    }
+
+    fn stop(&mut self) {
+        job::stop();
+    }
+
+    fn play_clip(&mut self, name: &str) -> Result<u32, ApiError> {
+        if !clip::exists(name) {
+            return Err(ApiError::not_found(format!("clip not found: {}", name)));
+        }
+        Ok(job::enqueue(job::Job::clip(name.to_string()))?)
+    }
+
+    fn play_pcm(&mut self, pcm: Vec<i16>) -> Result<u32, ApiError> {
+        Ok(job::enqueue(job::Job::play(pcm))?)
+    }
+
+    fn queue(&self) -> serde_json::Value {
+        serde_json::to_value(queue_response()).unwrap_or_default()
+    }
+
+    fn set_queue_max_len(&mut self, max_len: usize) {
+        job::set_max_len(max_len);
+    }
+
+    fn cancel(&mut self, id: u32) -> bool {
+        job::cancel(id)
+    }
+
+    fn flush(&mut self) -> usize {
+        job::flush()
+    }
+
+    fn clips(&self) -> Result<serde_json::Value, ApiError> {
+        Ok(serde_json::to_value(clip::library()?).map_err(anyhow::Error::from)?)
+    }
+
+    fn delete_clip(&mut self, name: &str) -> Result<(), ApiError> {
+        clip::validate_name(name).map_err(|e| ApiError::bad_request(e.to_string()))?;
+        if !clip::exists(name) {
+            return Err(ApiError::not_found(format!("clip not found: {}", name)));
+        }
+        Ok(clip::delete(name)?)
+    }
+
+    fn mixer(&self) -> mixer::Info {
+        audio::mixer().info()
+    }
+
+    fn set_duck_gain(&mut self, duck_gain: f32) {
+        audio::mixer().set_duck_gain(duck_gain);
+    }
+
+    fn set_time(&mut self, epoch: u64) -> Result<(), ApiError> {
+        clock::set(epoch).map_err(|e| ApiError::bad_request(e.to_string()))
+    }
+
+    fn telemetry(&self) -> Telemetry {
+        audio::stats()
+    }
+
+    fn status(&self) -> Result<serde_json::Value, ApiError> {
+        Ok(serde_json::to_value(status::collect()).map_err(anyhow::Error::from)?)
+    }
+
+    fn loudness(&self) -> loudness::Settings {
+        *global::LOUDNESS.get().unwrap().lock().unwrap()
+    }
+
+    fn set_loudness(&mut self, settings: loudness::Settings) {
+        *global::LOUDNESS.get().unwrap().lock().unwrap() = settings;
+    }
+
+    fn voice(&self) -> effect::Settings {
+        *global::VOICE_EFFECT.get().unwrap().lock().unwrap()
+    }
+
+    fn set_voice(&mut self, settings: effect::Settings) {
+        *global::VOICE_EFFECT.get().unwrap().lock().unwrap() = settings;
+    }
+
+    fn silence(&self) -> trim::Settings {
+        *global::SILENCE.get().unwrap().lock().unwrap()
+    }
+
+    fn set_silence(&mut self, settings: trim::Settings) {
+        *global::SILENCE.get().unwrap().lock().unwrap() = settings;
+    }
+
+    fn authenticate(&self, authorization: Option<&str>) -> Result<Scope, Denied> {
+        access::authenticate(authorization)
+    }
+
+    fn credentials(&self) -> Vec<auth::Info> {
+        access::list()
+    }
+
+    fn add_credential(
+        &mut self,
+        request: api::CredentialRequest,
+    ) -> Result<api::CredentialResponse, ApiError> {
+        let token = access::add(&request.name, request.scope, request.password.as_deref())?;
+        let kind = if token.is_some() {
+            auth::Kind::Token
+        } else {
+            auth::Kind::Password
+        };
+        Ok(api::CredentialResponse {
+            info: auth::Info {
+                name: request.name,
+                kind,
+                scope: request.scope,
+            },
+            token,
+        })
+    }
+
+    fn remove_credential(&mut self, name: &str) -> Result<bool, ApiError> {
+        Ok(access::remove(name)?)
+    }
+
+    fn tls(&self) -> Result<serde_json::Value, ApiError> {
+        Ok(serde_json::to_value(tls::status()).map_err(anyhow::Error::from)?)
+    }
+
+    fn set_tls(&mut self, enabled: bool) -> Result<(), ApiError> {
+        Ok(tls::set_enabled(enabled)?)
+    }
+
+    fn set_certificate(&mut self, identity: Identity) -> Result<(), ApiError> {
+        Ok(tls::set_custom(&identity)?)
+    }
+
+    fn clear_certificate(&mut self) -> Result<bool, ApiError> {
+        Ok(tls::clear_custom()?)
+    }
+
+    fn restart(&mut self) {
+        log::warn!("restart requested");
+        thread::spawn(|| {
+            thread::sleep(Duration::from_millis(global::RESTART_DELAY_MS));
+            unsafe { esp_idf_svc::sys::esp_restart() }
+        });
+    }
+
+    fn mqtt(&self) -> broker::Info {
+        mqtt::info()
+    }
+
+    fn set_mqtt(&mut self, settings: broker::Settings) -> Result<(), ApiError> {
+        Ok(mqtt::set_settings(settings)?)
+    }
+
+    fn socket(&self) -> line::Settings {
+        socket::settings()
+    }
+
+    fn set_socket(&mut self, settings: line::Settings) -> Result<(), ApiError> {
+        Ok(socket::set_settings(settings)?)
+    }
+}
+
+// 裸 PCM 的格式由 query 参数指定，例如 /api/play?rate=8000&channels=1&bits=16
+fn raw_pcm_format(uri: &str) -> anyhow::Result<wav::Format> {
+    let param = |key: &str, default: u32| -> anyhow::Result<u32> {
+        match query_param(uri, key) {
+            Some(v) => Ok(v.parse()?),
+            None => Ok(default),
+        }
+    };
+
+    let format = wav::Format {
+        channels: param("channels", 1)? as u16,
+        sample_rate: param("rate", global::SAMPLE_RATE)?,
+        bits_per_sample: param("bits", 16)? as u16,
+    };
+    format.validate()?;
+    Ok(format)
+}
+
+// 请求体超过 max_len 时返回 None
+fn read_body(req: &mut (impl Read + Headers), max_len: usize) -> anyhow::Result<Option<Vec<u8>>> {
+    let len = req.content_len().unwrap_or(0) as usize;
+    if len > max_len {
+        return Ok(None);
+    }
+
+    let mut buf = vec![0; len];
+    req.read_exact(&mut buf)
+        .map_err(|e| anyhow!("read body error: {:?}", e))?;
+    Ok(Some(buf))
+}
+
+fn queue_response() -> QueueResponse {
+    QueueResponse {
+        max_len: job::max_len(),
+        items: job::pending(),
+    }
+}
+
+// 检查 Authorization 请求头，权限不足时返回 401 / 403 并返回 None
+fn authorize<'a, 'r>(
+    req: Request<&'a mut EspHttpConnection<'r>>,
+    scope: Scope,
+) -> anyhow::Result<Option<Request<&'a mut EspHttpConnection<'r>>>> {
+    let Err(e) = access::authorize(req.header("Authorization"), scope) else {
+        return Ok(Some(req));
+    };
+    log::warn!("{} denied: {}", req.uri(), e);
+    let status = if e == Denied::Forbidden { 403 } else { 401 };
+    req.into_response(
+        status,
+        None,
+        &[("WWW-Authenticate", global::AUTH_CHALLENGE)],
+    )?
+    .write_all(e.to_string().as_bytes())?;
+    Ok(None)
+}
+
+// 返回播报 ID，队列已满时返回 503
+fn respond_id(
+    req: Request<&mut EspHttpConnection<'_>>,
+    result: anyhow::Result<u32>,
+) -> anyhow::Result<()> {
+    match result {
+        Ok(id) => {
+            req.into_ok_response()?
+                .write_all(&serde_json::to_vec(&IdResponse { id })?)?;
+        }
+        Err(e) if e.is::<job::Full>() => {
+            req.into_status_response(503)?
+                .write_all("Queue full".as_bytes())?;
+        }
+        Err(e) => {
+            req.into_status_response(500)?
+                .write_all(e.to_string().as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+// /api/queue/ID -> ID
+fn queue_id(uri: &str) -> Option<u32> {
+    let path = uri.split('?').next().unwrap_or(uri);
+    path.trim_start_matches("/api/queue/").parse().ok()
+}
+
+// /api/mixer/sources/ID -> ID
+fn source_id(uri: &str) -> Option<u32> {
+    let path = uri.split('?').next().unwrap_or(uri);
+    path.trim_start_matches("/api/mixer/sources/").parse().ok()
+}
+
+// /api/clips/名称?... -> 名称
+fn clip_name(uri: &str) -> &str {
+    let path = uri.split('?').next().unwrap_or(uri);
+    path.trim_start_matches("/api/clips/")
+}
+
+fn query_param<'a>(uri: &'a str, key: &str) -> Option<&'a str> {
+    let (_, query) = uri.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| v)
+}
+
+fn configuration() -> Configuration {
+    Configuration {
+        http_port: global::HTTP_PORT,
+        https_port: global::HTTPS_PORT,
+        ctrl_port: global::HTTP_CTRL_PORT,
         stack_size: global::STACK_SIZE,
+        max_uri_handlers: global::MAX_URI_HANDLERS,
+        uri_match_wildcard: true,
         ..Default::default()
-    };
+    }
+}
+
+// 启用 HTTPS 时依次尝试上传的证书和自签名证书，都无法使用时退回 HTTP
+fn create_server() -> anyhow::Result<EspHttpServer<'static>> {
+    if tls::is_enabled() {
+        for identity in tls::identities() {
+            let server_configuration = Configuration {
+                server_certificate: Some(x509(&identity.cert)),
+                private_key: Some(x509(&identity.key)),
+                ..configuration()
+            };
+            match EspHttpServer::new(&server_configuration) {
+                Ok(server) => {
+                    log::info!("https server started, {:?}", identity.fingerprint());
+                    tls::set_active(true);
+                    start_redirect()?;
+                    return Ok(server);
+                }
+                Err(e) => log::error!("https server error: {:?}", e),
+            }
+        }
+    }
+
+    tls::set_active(false);
+    Ok(EspHttpServer::new(&configuration())?)
+}
+
+// PEM 需以 \0 结尾，服务器运行期间一直使用
+fn x509(pem: &str) -> X509<'static> {
+    let mut bytes = pem.as_bytes().to_vec();
+    bytes.push(0);
+    X509::pem_until_nul(Box::leak(bytes.into_boxed_slice()))
+}
+
+// HTTPS 启用时 80 端口只把请求重定向到 https://
+fn start_redirect() -> anyhow::Result<()> {
+    let mut server = EspHttpServer::new(&Configuration {
+        http_port: global::HTTP_PORT,
+        ctrl_port: global::REDIRECT_CTRL_PORT,
+        max_uri_handlers: 4,
+        uri_match_wildcard: true,
+        ..Default::default()
+    })?;
 
-    Ok(EspHttpServer::new(&server_configuration)?)
+    for method in [Method::Get, Method::Post, Method::Put, Method::Delete] {
+        _ = server.fn_handler::<anyhow::Error, _>("/*", method, |req| {
+            let ip = wifi::status().ip.map(|ip| ip.to_string());
+            let host = req
+                .header("Host")
+                .map(|host| host.split(':').next().unwrap_or(host).to_string())
+                .or(ip)
+                .unwrap_or_default();
+            let location = format!("https://{}{}", host, req.uri());
+            // 308 保留请求方法和请求体
+            req.into_response(308, None, &[("Location", &location)])?;
+            Ok(())
+        })?;
+    }
+
+    core::mem::forget(server);
+    Ok(())
 }
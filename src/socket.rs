@@ -0,0 +1,173 @@
+// TCP / UDP 文本协议服务，消息格式见 line.rs
+// 设置保存在 NVS 中，修改后重启生效；启用认证时命令需先通过 "!auth ..." 认证
+
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::access;
+use crate::api::{Device, Limits};
+use crate::global;
+use crate::line::{self, Lines, Settings};
+use crate::nvs;
+use crate::protocol;
+
+// 当前的 TCP 连接数
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+
+pub fn settings() -> Settings {
+    read()
+        .unwrap_or_else(|e| {
+            log::warn!("read socket settings error: {:?}", e);
+            None
+        })
+        .unwrap_or_default()
+}
+
+pub fn set_settings(settings: Settings) -> anyhow::Result<()> {
+    settings.validate(global::RESERVED_PORTS)?;
+    nvs::write(
+        global::SOCKET_NVS_NAMESPACE,
+        global::SOCKET_SETTINGS_KEY,
+        &serde_json::to_vec(&settings)?,
+    )
+}
+
+pub fn start(device: impl Device + Clone + Send + 'static, limits: Limits) -> anyhow::Result<()> {
+    let settings = settings();
+    if settings.tcp || settings.udp {
+        settings.validate(global::RESERVED_PORTS)?;
+    }
+
+    if settings.tcp {
+        let listener = TcpListener::bind(("0.0.0.0", settings.tcp_port))?;
+        log::info!("line protocol listening on tcp port {}", settings.tcp_port);
+        let device = device.clone();
+        thread::Builder::new()
+            .name("line_tcp".to_string())
+            .stack_size(global::SOCKET_STACK_SIZE)
+            .spawn(move || accept(listener, device, limits))?;
+    }
+
+    if settings.udp {
+        let socket = UdpSocket::bind(("0.0.0.0", settings.udp_port))?;
+        log::info!("line protocol listening on udp port {}", settings.udp_port);
+        thread::Builder::new()
+            .name("line_udp".to_string())
+            .stack_size(global::STACK_SIZE)
+            .spawn(move || receive(socket, device, limits))?;
+    }
+    Ok(())
+}
+
+fn accept(listener: TcpListener, device: impl Device + Clone + Send + 'static, limits: Limits) {
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                log::warn!("line tcp accept error: {:?}", e);
+                continue;
+            }
+        };
+        if CONNECTIONS.load(Ordering::Relaxed) >= global::SOCKET_MAX_CONNECTIONS {
+            log::warn!("too many line protocol connections");
+            _ = stream.write_all(b"ERR too_many_connections\n");
+            continue;
+        }
+
+        CONNECTIONS.fetch_add(1, Ordering::Relaxed);
+        let mut device = device.clone();
+        let spawned = thread::Builder::new()
+            .name("line_conn".to_string())
+            .stack_size(global::STACK_SIZE)
+            .spawn(move || {
+                if let Err(e) = serve(&mut stream, &mut device, &limits) {
+                    log::warn!("line tcp connection error: {:?}", e);
+                }
+                CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+            });
+        if let Err(e) = spawned {
+            log::error!("line tcp spawn error: {:?}", e);
+            CONNECTIONS.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn serve(stream: &mut TcpStream, device: &mut impl Device, limits: &Limits) -> anyhow::Result<()> {
+    if let Ok(peer) = stream.peer_addr() {
+        log::info!("line tcp client connected: {}", peer);
+    }
+    let timeout = Duration::from_millis(global::SOCKET_IDLE_TIMEOUT_MS);
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut session = protocol::connect(device);
+    let mut revision = access::revision();
+    let mut lines = Lines::new(global::MAX_TEXT_LEN);
+    let mut buf = [0u8; 512];
+    loop {
+        let len = match stream.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            // 长时间没有数据的客户端被断开，释放连接数
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                log::info!("line tcp client idle, closing");
+                _ = stream.write_all(b"ERR timeout idle connection closed\n");
+                return Ok(());
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for data in lines.push(&buf[..len]) {
+            // 凭据修改后重新校验，被删除或降级的凭据立即失去权限
+            if revision != access::revision() {
+                revision = access::revision();
+                session.refresh(access::authenticate);
+            }
+            let reply = match data {
+                Ok(data) => line::handle(device, limits, &mut session, &data),
+                Err(e) => Some(format!("ERR {} {}", e.code, e.message)),
+            };
+            if let Some(reply) = reply {
+                stream.write_all(format!("{}\n", reply).as_bytes())?;
+            }
+        }
+    }
+}
+
+// 每个数据报为一条消息，ack 发回给发送方
+fn receive(socket: UdpSocket, mut device: impl Device, limits: Limits) {
+    let max_len = global::MAX_TEXT_LEN + global::SOCKET_AUTH_MAX_LEN;
+    let mut buf = vec![0u8; max_len + 1];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) => {
+                log::warn!("line udp receive error: {:?}", e);
+                continue;
+            }
+        };
+        // 超长的数据报被截断
+        let reply = if len > max_len {
+            Some(format!(
+                "ERR payload_too_large datagram exceeds {} bytes",
+                max_len
+            ))
+        } else {
+            line::handle_datagram(&mut device, &limits, &buf[..len])
+        };
+        if let Some(reply) = reply {
+            if let Err(e) = socket.send_to(format!("{}\n", reply).as_bytes(), peer) {
+                log::warn!("line udp send to {} error: {:?}", peer, e);
+            }
+        }
+    }
+}
+
+fn read() -> anyhow::Result<Option<Settings>> {
+    match nvs::read(global::SOCKET_NVS_NAMESPACE, global::SOCKET_SETTINGS_KEY)? {
+        Some(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,39 @@
+// 设备状态：固件版本、运行时间、内存、播报队列、WiFi 与分区
+
+use serde::Serialize;
+
+use crate::audio;
+use crate::job;
+use crate::tts;
+use crate::utils::{self, Heap, Partition};
+use crate::wifi;
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub version: &'static str,
+    pub idf_version: String,
+    pub uptime_secs: u64,
+    pub heap: Heap,
+    pub volume: u8,
+    // 等待合成的播报数量
+    pub queue_len: usize,
+    pub speaking: bool,
+    pub wifi: wifi::Status,
+    pub voice_loaded: bool,
+    pub partitions: Vec<Partition>,
+}
+
+pub fn collect() -> Status {
+    Status {
+        version: env!("CARGO_PKG_VERSION"),
+        idf_version: utils::idf_version(),
+        uptime_secs: utils::uptime_secs(),
+        heap: utils::heap(),
+        volume: audio::volume(),
+        queue_len: job::pending_len(),
+        speaking: audio::is_speaking(),
+        wifi: wifi::status(),
+        voice_loaded: tts::is_loaded(),
+        partitions: utils::partitions(),
+    }
+}
@@ -0,0 +1,49 @@
+use std::ffi::CString;
+
+use esp_idf_svc::sys::{
+    esp, esp_vfs_fat_info, esp_vfs_fat_mount_config_t, esp_vfs_fat_spiflash_mount_rw_wl,
+    wl_handle_t, EspError, WL_INVALID_HANDLE,
+};
+
+use crate::global;
+
+// 挂载 storage 分区（FAT + wear levelling），首次挂载失败时自动格式化
+pub fn mount() -> Result<(), EspError> {
+    let base_path = CString::new(global::STORAGE_BASE_PATH).unwrap();
+    let partition_label = CString::new(global::STORAGE_PARTITION).unwrap();
+
+    let mount_config = esp_vfs_fat_mount_config_t {
+        format_if_mount_failed: true,
+        max_files: 4,
+        allocation_unit_size: 4096,
+        ..Default::default()
+    };
+    let mut wl_handle = WL_INVALID_HANDLE as wl_handle_t;
+
+    esp!(unsafe {
+        esp_vfs_fat_spiflash_mount_rw_wl(
+            base_path.as_ptr(),
+            partition_label.as_ptr(),
+            &mount_config,
+            &mut wl_handle,
+        )
+    })?;
+    log::info!(
+        "storage partition {} mounted at {}",
+        global::STORAGE_PARTITION,
+        global::STORAGE_BASE_PATH
+    );
+
+    Ok(())
+}
+
+// 返回文件系统 (总容量, 剩余容量)，单位字节
+pub fn info() -> Result<(u64, u64), EspError> {
+    let base_path = CString::new(global::STORAGE_BASE_PATH).unwrap();
+    let mut total = 0u64;
+    let mut free = 0u64;
+
+    esp!(unsafe { esp_vfs_fat_info(base_path.as_ptr(), &mut total, &mut free) })?;
+
+    Ok((total, free))
+}
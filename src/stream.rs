@@ -0,0 +1,168 @@
+// 扬声器输出的实时监听：通过 WebSocket 推送写入 sink 的 PCM
+//
+// 每个监听者有独立的线程和帧队列，发送慢时只丢弃该监听者的帧，不会阻塞音频线程；
+// 持续跟不上实时速度的监听者会被断开
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+use esp_idf_svc::http::server::ws::EspHttpWsDetachedSender;
+use esp_idf_svc::ws::FrameType;
+use serde::Serialize;
+
+use crate::access;
+use crate::audio::{self, Tap};
+use crate::auth::Scope;
+use crate::global;
+
+// 当前监听者的 session，连接关闭时移除并通知推送线程退出
+static LISTENERS: Mutex<Vec<(i32, Arc<AtomicBool>)>> = Mutex::new(Vec::new());
+
+// 连接后先发送的文本帧，之后为 16 位小端 PCM 二进制帧
+#[derive(Debug, Serialize)]
+struct Format {
+    sample_rate: u32,
+    channels: u16,
+    bits: u16,
+}
+
+// 为新连接启动推送线程，监听者已满时返回 false，已在监听的连接不重复启动
+// authorization 为认证时使用的凭据，凭据被删除或降级后断开
+pub fn start(
+    session: i32,
+    mut sender: EspHttpWsDetachedSender,
+    authorization: Option<String>,
+) -> anyhow::Result<bool> {
+    let active = Arc::new(AtomicBool::new(true));
+    {
+        let mut listeners = LISTENERS.lock().unwrap();
+        if listeners.iter().any(|(s, _)| *s == session) {
+            return Ok(true);
+        }
+        if listeners.len() >= global::STREAM_MAX_LISTENERS {
+            return Ok(false);
+        }
+        listeners.push((session, active.clone()));
+    }
+
+    let format = Format {
+        sample_rate: global::SAMPLE_RATE,
+        channels: 1,
+        bits: 16,
+    };
+    let result = sender
+        .send(FrameType::Text(false), &serde_json::to_vec(&format)?)
+        .map_err(anyhow::Error::from)
+        .and_then(|()| {
+            let tap = audio::tap(global::STREAM_QUEUE_LEN);
+            let active = active.clone();
+            Builder::new()
+                .name("audio_stream".to_string())
+                .stack_size(global::STREAM_STACK_SIZE)
+                .spawn(move || {
+                    run(sender, tap, authorization, &active);
+                    remove(&active);
+                })?;
+            Ok(())
+        });
+
+    if result.is_err() {
+        remove(&active);
+    }
+    result.map(|()| true)
+}
+
+// 连接关闭时调用，推送线程在下一帧或超时检查时退出
+pub fn stop(session: i32) {
+    LISTENERS.lock().unwrap().retain(|(s, active)| {
+        if *s == session {
+            active.store(false, Ordering::Relaxed);
+        }
+        *s != session
+    });
+}
+
+// 只移除自己的记录，session 可能已被新的连接复用
+fn remove(active: &Arc<AtomicBool>) {
+    LISTENERS
+        .lock()
+        .unwrap()
+        .retain(|(_, a)| !Arc::ptr_eq(a, active));
+}
+
+fn run(
+    mut sender: EspHttpWsDetachedSender,
+    tap: Tap,
+    authorization: Option<String>,
+    active: &AtomicBool,
+) {
+    log::info!("audio stream listener connected");
+    let mut revision = access::revision();
+    let mut chunk: Vec<u8> = Vec::with_capacity(global::STREAM_CHUNK_SAMPLES * 2);
+    // 统计窗口的开始时间及当时的丢帧数
+    let mut window = (Instant::now(), 0usize);
+
+    loop {
+        // 连接已关闭
+        if !active.load(Ordering::Relaxed) {
+            break;
+        }
+
+        // 凭据修改后重新校验
+        if revision != access::revision() {
+            revision = access::revision();
+            if let Err(e) = access::authorize(authorization.as_deref(), Scope::Speak) {
+                log::warn!("audio stream listener revoked: {}", e);
+                _ = sender.send(FrameType::Close, &[]);
+                break;
+            }
+        }
+
+        let frame = match tap.rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(frame) => frame,
+            // 播放结束时发出剩余数据，并检查连接是否已关闭
+            Err(RecvTimeoutError::Timeout) => {
+                if sender.is_closed() || !flush(&mut sender, &mut chunk) {
+                    break;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // 一个窗口内丢帧过多说明持续跟不上实时速度
+        if window.0.elapsed() >= Duration::from_millis(global::STREAM_WINDOW_MS) {
+            let dropped = tap.dropped.load(Ordering::Relaxed);
+            if dropped - window.1 > global::STREAM_MAX_DROPPED_FRAMES {
+                log::warn!("audio stream listener too slow, {} frames dropped", dropped);
+                _ = sender.send(FrameType::Close, &[]);
+                break;
+            }
+            window = (Instant::now(), dropped);
+        }
+
+        for sample in frame {
+            chunk.extend_from_slice(&sample.to_le_bytes());
+        }
+        if chunk.len() >= global::STREAM_CHUNK_SAMPLES * 2 && !flush(&mut sender, &mut chunk) {
+            break;
+        }
+    }
+    log::info!(
+        "audio stream listener closed, {} frames dropped",
+        tap.dropped.load(Ordering::Relaxed)
+    );
+}
+
+// 发送缓存的 PCM，连接已断开时返回 false
+fn flush(sender: &mut EspHttpWsDetachedSender, chunk: &mut Vec<u8>) -> bool {
+    if chunk.is_empty() {
+        return true;
+    }
+    let ok = sender.send(FrameType::Binary(false), chunk).is_ok();
+    chunk.clear();
+    ok
+}
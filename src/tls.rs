@@ -0,0 +1,120 @@
+// HTTPS：启用状态与证书保存在 NVS 中，首次启动时生成自签名证书，也可上传证书 / 私钥
+// 修改后重启生效
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::cert::{self, Identity};
+use crate::global;
+use crate::nvs;
+use crate::utils;
+use crate::wifi;
+
+// 当前 HTTP 服务器是否使用 TLS
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Settings {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    // 重启后是否使用 HTTPS
+    pub enabled: bool,
+    // 当前是否使用 HTTPS
+    pub active: bool,
+    // 使用上传的证书
+    pub custom: bool,
+    // 当前证书的 SHA-256 指纹
+    pub fingerprint: Option<String>,
+}
+
+// 首次启动时生成自签名证书，需在 WiFi 启动后调用以获得真随机数
+pub fn init() -> anyhow::Result<()> {
+    if read::<Identity>(global::TLS_GENERATED_KEY)?.is_some() {
+        return Ok(());
+    }
+
+    log::info!("generating self-signed certificate");
+    let mut seed = [0u8; 32];
+    let mut serial = [0u8; 16];
+    utils::random_bytes(&mut seed);
+    utils::random_bytes(&mut serial);
+    let identity = cert::generate(&seed, &serial, global::WIFI_AP_NAME, wifi::status().ip)?;
+    write(global::TLS_GENERATED_KEY, &identity)?;
+    log::info!("certificate fingerprint: {:?}", identity.fingerprint());
+    Ok(())
+}
+
+pub fn is_enabled() -> bool {
+    read::<Settings>(global::TLS_SETTINGS_KEY)
+        .unwrap_or_else(|e| {
+            log::warn!("read tls settings error: {:?}", e);
+            None
+        })
+        .unwrap_or_default()
+        .enabled
+}
+
+pub fn set_enabled(enabled: bool) -> anyhow::Result<()> {
+    write(global::TLS_SETTINGS_KEY, &Settings { enabled })
+}
+
+pub fn set_active(active: bool) {
+    ACTIVE.store(active, Ordering::Relaxed);
+}
+
+// 按优先级返回可用的证书：上传的证书，自签名证书
+// 私钥与证书不匹配等无效的证书被跳过
+pub fn identities() -> Vec<Identity> {
+    [global::TLS_CUSTOM_KEY, global::TLS_GENERATED_KEY]
+        .iter()
+        .filter_map(|key| {
+            read::<Identity>(key).unwrap_or_else(|e| {
+                log::warn!("read certificate {} error: {:?}", key, e);
+                None
+            })
+        })
+        .filter(|identity| match identity.validate() {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("invalid certificate: {:?}", e);
+                false
+            }
+        })
+        .collect()
+}
+
+pub fn set_custom(identity: &Identity) -> anyhow::Result<()> {
+    identity.validate()?;
+    write(global::TLS_CUSTOM_KEY, identity)
+}
+
+// 改回自签名证书，没有上传的证书时返回 false
+pub fn clear_custom() -> anyhow::Result<bool> {
+    nvs::remove(global::TLS_NVS_NAMESPACE, global::TLS_CUSTOM_KEY)
+}
+
+pub fn status() -> Status {
+    let custom = read::<Identity>(global::TLS_CUSTOM_KEY).ok().flatten();
+    Status {
+        enabled: is_enabled(),
+        active: ACTIVE.load(Ordering::Relaxed),
+        custom: custom.is_some(),
+        fingerprint: identities().first().and_then(Identity::fingerprint),
+    }
+}
+
+fn read<T: DeserializeOwned>(key: &str) -> anyhow::Result<Option<T>> {
+    match nvs::read(global::TLS_NVS_NAMESPACE, key)? {
+        Some(buf) => Ok(Some(serde_json::from_slice(&buf)?)),
+        None => Ok(None),
+    }
+}
+
+fn write<T: Serialize>(key: &str, value: &T) -> anyhow::Result<()> {
+    nvs::write(global::TLS_NVS_NAMESPACE, key, &serde_json::to_vec(value)?)
+}
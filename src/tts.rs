@@ -1,10 +1,29 @@
 use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 
 use std::ffi::CString;
 
 use esp_idf_svc::sys::esp_sr;
 
+use crate::audio::{self, Packet};
+use crate::clip;
+use crate::decoder;
+use crate::earcon::Earcon;
+use crate::effect::Effect;
+use crate::event::{self, Event};
+use crate::global;
+use crate::job::{self, is_stopped, Job};
+use crate::loudness::Normalizer;
+use crate::markup::{self, Segment};
+use crate::trim::Trimmer;
+
+// 每次发送到音频线程的采样点数
+const CHUNK_SAMPLES: usize = 1024;
+
+// 语音数据已加载
+static LOADED: AtomicBool = AtomicBool::new(false);
+
 pub struct TTS {
     mmap_handle: esp_sr::esp_partition_mmap_handle_t,
     tts_handle: esp_sr::esp_tts_handle_t,
@@ -65,6 +84,7 @@ impl TTS {
             tts_handle = esp_sr::esp_tts_create(voice);
             log::info!("esp_tts_create");
         }
+        LOADED.store(!tts_handle.is_null(), Ordering::Relaxed);
 
         TTS {
             mmap_handle,
@@ -72,41 +92,180 @@ impl TTS {
         }
     }
 
-    pub fn play(&mut self, data: String, tx: mpsc::Sender<&[u8]>) {
+    // 文本中的 [clip:名称] 标记按顺序插入音频片段
+    fn speak(&mut self, id: u32, data: &str, tx: &mpsc::SyncSender<Packet>) {
+        for segment in markup::parse(data) {
+            match segment {
+                Segment::Text(text) => self.synthesize(id, text, tx),
+                Segment::Clip(name) => self.play_clip(id, name, tx),
+            }
+        }
+    }
+
+    fn synthesize(&mut self, id: u32, data: &str, tx: &mpsc::SyncSender<Packet>) {
         let tts_handle = self.tts_handle;
 
-        unsafe {
-            let prompt = CString::new(data.as_str()).unwrap();
-            log::info!("prompt: {}", prompt.to_str().unwrap());
+        // 请求中的文本已校验，这里仍不能因 \0 导致 panic
+        let prompt = match CString::new(data) {
+            Ok(prompt) => prompt,
+            Err(e) => {
+                log::error!("skip text segment: {:?}", e);
+                return;
+            }
+        };
+        log::info!("prompt: {}", data);
 
+        unsafe {
             if esp_sr::esp_tts_parse_chinese(tts_handle, prompt.as_ptr()) == 0 {
                 log::error!("esp_tts_parse_chinese fail");
             }
 
+            let mut chain = SpeechChain::new();
             let mut len = [0i32; 1];
             loop {
+                if is_stopped(id) {
+                    esp_sr::esp_tts_stream_reset(tts_handle);
+                    break;
+                }
+
                 let pcm_data = esp_sr::esp_tts_stream_play(tts_handle, len.as_mut_ptr(), 3);
                 if len[0] <= 0 {
                     break;
                 }
 
                 // play sound
-                let pcm_slice: &[u8] = slice::from_raw_parts(
-                    pcm_data as *const u8, // 转为字节指针
-                    (len[0] * 2) as usize, // 总字节数
-                );
+                let pcm_slice: &[i16] = slice::from_raw_parts(pcm_data, len[0] as usize);
+                let pcm = chain.process(pcm_slice);
 
-                _ = tx.send(pcm_slice);
+                if !pcm.is_empty() {
+                    audio::send(tx, Packet::Data(id, pcm));
+                }
+            }
+
+            if !is_stopped(id) {
+                let pcm = chain.finish();
+                if !pcm.is_empty() {
+                    audio::send(tx, Packet::Data(id, pcm));
+                }
             }
         }
     }
 
-    pub fn play_with_rx(&mut self, rx: mpsc::Receiver<String>, tx: mpsc::Sender<&[u8]>) {
+    fn play_clip(&mut self, id: u32, name: &str, tx: &mpsc::SyncSender<Packet>) {
+        match clip::open(name) {
+            Ok(stream) => self.play_stream(id, name, stream, Some(normalizer()), tx),
+            Err(e) => log::warn!("open clip {} error: {:?}", name, e),
+        }
+    }
+
+    fn play_earcon(&mut self, id: u32, earcon: &Earcon, tx: &mpsc::SyncSender<Packet>) {
+        match earcon.open() {
+            // 提示音保持原有音量
+            Ok(Some(stream)) => self.play_stream(id, &earcon.to_string(), stream, None, tx),
+            Ok(None) => {}
+            Err(e) => log::warn!("open earcon {} error: {:?}", earcon, e),
+        }
+    }
+
+    // 逐块解码播放，避免整段音频加载到内存
+    fn play_stream(
+        &mut self,
+        id: u32,
+        name: &str,
+        mut stream: decoder::Stream,
+        mut normalizer: Option<Normalizer>,
+        tx: &mpsc::SyncSender<Packet>,
+    ) {
+        while !is_stopped(id) {
+            match stream.next_chunk() {
+                Ok(Some(mut pcm)) => {
+                    if let Some(normalizer) = normalizer.as_mut() {
+                        normalizer.process(&mut pcm);
+                    }
+                    audio::send(tx, Packet::Data(id, pcm));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("decode {} error: {:?}", name, e);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn play(&mut self, id: u32, pcm: Vec<i16>, tx: &mpsc::SyncSender<Packet>) {
+        for chunk in pcm.chunks(CHUNK_SAMPLES) {
+            if is_stopped(id) {
+                break;
+            }
+            audio::send(tx, Packet::Data(id, chunk.to_vec()));
+        }
+    }
+
+    pub fn play_queue(&mut self, tx: mpsc::SyncSender<Packet>) {
         loop {
-            let data = rx.recv().unwrap();
-            self.play(data, tx.clone());
+            let (id, job) = job::next();
+            if is_stopped(id) {
+                event::emit(Event::Stopped { id });
+                continue;
+            }
+
+            audio::send(&tx, Packet::Begin(id));
+            match job {
+                Job::Speak { text, earcons } => {
+                    self.play_earcon(id, &earcons.pre, &tx);
+                    self.speak(id, &text, &tx);
+                    self.play_earcon(id, &earcons.post, &tx);
+                }
+                Job::Play { pcm } => self.play(id, pcm, &tx),
+                Job::Clip { name } => self.play_clip(id, &name, &tx),
+            }
+            audio::send(&tx, Packet::End(id));
+        }
+    }
+}
+
+pub fn is_loaded() -> bool {
+    LOADED.load(Ordering::Relaxed)
+}
+
+// 每个文本段 / 音频片段单独统计响度
+fn normalizer() -> Normalizer {
+    Normalizer::new(*global::LOUDNESS.get().unwrap().lock().unwrap())
+}
+
+// 合成语音的处理链：首尾静音裁剪 -> 响度归一化 -> 变速变调
+struct SpeechChain {
+    trimmer: Trimmer,
+    normalizer: Normalizer,
+    effect: Effect,
+}
+
+impl SpeechChain {
+    fn new() -> Self {
+        let silence = *global::SILENCE.get().unwrap().lock().unwrap();
+        let effect = *global::VOICE_EFFECT.get().unwrap().lock().unwrap();
+        SpeechChain {
+            trimmer: Trimmer::new(silence, global::SAMPLE_RATE),
+            normalizer: normalizer(),
+            effect: Effect::new(effect, global::SAMPLE_RATE),
         }
     }
+
+    fn process(&mut self, pcm: &[i16]) -> Vec<i16> {
+        let mut pcm = self.trimmer.process(pcm);
+        self.normalizer.process(&mut pcm);
+        self.effect.process(&pcm)
+    }
+
+    // 输出各级缓存中剩余的数据
+    fn finish(&mut self) -> Vec<i16> {
+        let mut tail = self.trimmer.finish();
+        self.normalizer.process(&mut tail);
+        let mut pcm = self.effect.process(&tail);
+        pcm.extend(self.effect.flush());
+        pcm
+    }
 }
 
 impl Drop for TTS {
@@ -1,16 +1,17 @@
 use std::sync::mpsc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use esp_idf_svc::sys::EspError;
 
 use core::cell::UnsafeCell;
 
 use lvgl::style::Style;
-use lvgl::widgets::Label;
-use lvgl::{Align, Color, Display, DrawBuffer, Part, Widget};
+use lvgl::widgets::{Bar, Label};
+use lvgl::{Align, AnimationState, Color, Display, DrawBuffer, Part, Widget};
 
 use cstr_core::CString;
 
+use crate::audio;
 use crate::global;
 
 fn init_spi() -> Result<(), EspError> {
@@ -134,13 +135,34 @@ impl UI {
         lbl.set_align(Align::Center, 0, 0);
         lbl.set_text(CString::new("Rust lvgl demo").unwrap().as_c_str());
 
+        // VU meter of the audio output, see audio::stats
+        let mut vu = Bar::create(&mut screen).unwrap();
+        vu.set_size(global::DISPLAY_WIDTH as i16 - 40, 12);
+        vu.set_align(Align::BottomMid, 0, -20);
+        vu.set_range(0, 100);
+
+        let mut last = Instant::now();
+        let mut vu_value = 0;
         loop {
-            let data = rx.recv().unwrap();
-            log::info!("lvgl recv");
-            let start = Instant::now();
+            match rx.recv_timeout(Duration::from_millis(global::UI_REFRESH_MS)) {
+                Ok(data) => {
+                    log::info!("lvgl recv");
+                    lbl.set_text(CString::new(data).unwrap().as_c_str());
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            let value = audio::stats().vu_percent() as i32;
+            if value != vu_value {
+                vu_value = value;
+                vu.set_value(value, AnimationState::OFF);
+            }
+
+            let now = Instant::now();
             lvgl::task_handler();
-            lbl.set_text(CString::new(data).unwrap().as_c_str());
-            lvgl::tick_inc(Instant::now().duration_since(start));
+            lvgl::tick_inc(now.duration_since(last));
+            last = now;
         }
     }
 
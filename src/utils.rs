@@ -3,35 +3,82 @@ use std::ptr;
 use std::ffi::CStr;
 
 use esp_idf_svc::sys::esp_sr;
+use serde::Serialize;
 
-pub fn log_heap() {
+// 空闲内存 (字节)
+#[derive(Debug, Clone, Serialize)]
+pub struct Heap {
+    pub internal_free: usize,
+    pub psram_free: usize,
+    // 启动以来的最小空闲内存
+    pub min_free: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Partition {
+    pub label: String,
+    #[serde(rename = "type")]
+    pub type_: u8,
+    pub subtype: u8,
+    pub offset: u32,
+    pub size: u32,
+}
+
+pub fn heap() -> Heap {
     unsafe {
-        use esp_idf_svc::sys::{heap_caps_get_free_size, MALLOC_CAP_INTERNAL, MALLOC_CAP_SPIRAM};
+        use esp_idf_svc::sys::{
+            esp_get_minimum_free_heap_size, heap_caps_get_free_size, MALLOC_CAP_INTERNAL,
+            MALLOC_CAP_SPIRAM,
+        };
 
-        log::info!(
-            "Free SPIRAM heap size: {}",
-            heap_caps_get_free_size(MALLOC_CAP_SPIRAM)
-        );
-        log::info!(
-            "Free INTERNAL heap size: {}",
-            heap_caps_get_free_size(MALLOC_CAP_INTERNAL)
-        );
+        Heap {
+            internal_free: heap_caps_get_free_size(MALLOC_CAP_INTERNAL),
+            psram_free: heap_caps_get_free_size(MALLOC_CAP_SPIRAM),
+            min_free: esp_get_minimum_free_heap_size(),
+        }
     }
 }
 
-pub fn print_partitions() {
+pub fn log_heap() {
+    let heap = heap();
+    log::info!("Free SPIRAM heap size: {}", heap.psram_free);
+    log::info!("Free INTERNAL heap size: {}", heap.internal_free);
+}
+
+// 启动以来的秒数
+pub fn uptime_secs() -> u64 {
+    unsafe { esp_idf_svc::sys::esp_timer_get_time() as u64 / 1_000_000 }
+}
+
+// 硬件随机数，WiFi 启动后为真随机数
+pub fn random_bytes(buf: &mut [u8]) {
+    unsafe { esp_idf_svc::sys::esp_fill_random(buf.as_mut_ptr().cast(), buf.len()) }
+}
+
+// 出厂 MAC 地址，作为设备的唯一 ID，例如 "a1b2c3d4e5f6"
+pub fn device_id() -> String {
+    let mut mac = [0u8; 6];
+    unsafe { esp_idf_svc::sys::esp_efuse_mac_get_default(mac.as_mut_ptr()) };
+    mac.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn idf_version() -> String {
+    unsafe {
+        CStr::from_ptr(esp_idf_svc::sys::esp_get_idf_version())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+pub fn partitions() -> Vec<Partition> {
+    let mut partitions = Vec::new();
     unsafe {
-        log::info!("esp_partition_find_first");
         // 开始查找：传 null 表示从头开始
         let mut iterator = esp_sr::esp_partition_find(
             esp_sr::esp_partition_type_t_ESP_PARTITION_TYPE_ANY,
             esp_sr::esp_partition_subtype_t_ESP_PARTITION_SUBTYPE_ANY,
             ptr::null(),
         );
-        if iterator.is_null() {
-            log::error!("Couldn't find any partitions!");
-            return;
-        }
 
         while !iterator.is_null() {
             let part = esp_sr::esp_partition_get(iterator);
@@ -39,16 +86,14 @@ pub fn print_partitions() {
                 // 获取分区信息
                 let partition = *part; // 解引用 C struct
                 let name_cstr = CStr::from_ptr(partition.label.as_ptr());
-                let name = name_cstr.to_string_lossy();
-
-                log::info!(
-                    "Partition: name={}, type=0x{:X}, subtype=0x{:X}, offset=0x{:X}, size={} bytes",
-                    name,
-                    partition.type_ as u8,
-                    partition.subtype as u8,
-                    partition.address,
-                    partition.size
-                );
+
+                partitions.push(Partition {
+                    label: name_cstr.to_string_lossy().into_owned(),
+                    type_: partition.type_ as u8,
+                    subtype: partition.subtype as u8,
+                    offset: partition.address,
+                    size: partition.size,
+                });
             }
 
             // 继续查找下一个
@@ -56,4 +101,25 @@ pub fn print_partitions() {
         }
         esp_sr::esp_partition_iterator_release(iterator);
     }
+    partitions
+}
+
+pub fn print_partitions() {
+    log::info!("esp_partition_find_first");
+    let partitions = partitions();
+    if partitions.is_empty() {
+        log::error!("Couldn't find any partitions!");
+        return;
+    }
+
+    for partition in partitions {
+        log::info!(
+            "Partition: name={}, type=0x{:X}, subtype=0x{:X}, offset=0x{:X}, size={} bytes",
+            partition.label,
+            partition.type_,
+            partition.subtype,
+            partition.offset,
+            partition.size
+        );
+    }
 }
@@ -0,0 +1,164 @@
+// 唤醒词检测：麦克风数据经 AFE 处理后由 WakeNet 检测唤醒词
+// 唤醒后播放提示音，屏幕显示聆听状态，并发出 Event::Wake
+// 聆听期间由 MultiNet 识别命令词，执行命令表中对应的动作
+// 扬声器输出作为 AEC 参考信号，播报期间也能唤醒并打断当前播报
+
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+
+use crate::action::Action;
+use crate::afe::Afe;
+use crate::audio;
+use crate::clip;
+use crate::command::{self, Command};
+use crate::event::{self, Event};
+use crate::global;
+use crate::job;
+use crate::mic;
+use crate::multinet::{Detect, MultiNet};
+
+pub fn start(ui_tx: mpsc::Sender<String>) -> anyhow::Result<()> {
+    let afe = Afe::new()?;
+    // 没有命令词模型时只检测唤醒词
+    let multinet = match init_multinet(&afe) {
+        Ok(multinet) => Some(multinet),
+        Err(e) => {
+            log::error!("multinet init error: {:?}", e);
+            None
+        }
+    };
+    let mic_rx = mic::subscribe();
+    let ref_rx = audio::subscribe_output();
+
+    Builder::new()
+        .name("afe_feed".to_string())
+        .spawn(move || feed(afe, mic_rx, ref_rx))?;
+    Builder::new()
+        .name("afe_fetch".to_string())
+        .stack_size(global::WAKE_STACK_SIZE)
+        .spawn(move || fetch(afe, multinet, ui_tx))?;
+
+    Ok(())
+}
+
+fn init_multinet(afe: &Afe) -> anyhow::Result<MultiNet> {
+    let mut multinet = MultiNet::new(afe.models(), global::WAKE_LISTEN_MS as i32)?;
+    if multinet.chunksize() != afe.fetch_chunksize() {
+        bail!(
+            "multinet chunksize {} != afe fetch chunksize {}",
+            multinet.chunksize(),
+            afe.fetch_chunksize()
+        );
+    }
+    multinet.set_commands(&command::list())?;
+    Ok(multinet)
+}
+
+// 按 AFE 要求的长度送入麦克风数据与参考信号
+// 没有播放时参考信号为静音
+fn feed(afe: Afe, mic_rx: mpsc::Receiver<Vec<i16>>, ref_rx: mpsc::Receiver<Vec<i16>>) {
+    let chunk = afe.feed_chunksize();
+    let mut mic = Vec::with_capacity(chunk * 2);
+    let mut reference = VecDeque::new();
+    let mut buf = Vec::with_capacity(chunk * 2);
+    for frame in mic_rx {
+        reference.extend(ref_rx.try_iter().flatten());
+        if reference.len() > global::AEC_REF_MAX_SAMPLES {
+            reference.drain(..reference.len() - global::AEC_REF_MAX_SAMPLES);
+        }
+
+        mic.extend_from_slice(&frame);
+        while mic.len() >= chunk {
+            buf.clear();
+            for sample in mic.drain(..chunk) {
+                buf.push(sample);
+                buf.push(reference.pop_front().unwrap_or(0));
+            }
+            afe.feed(&buf);
+        }
+    }
+}
+
+fn fetch(afe: Afe, mut multinet: Option<MultiNet>, ui_tx: mpsc::Sender<String>) {
+    let mut commands = command::list();
+    // 正在聆听命令时为聆听截止时间
+    let mut listening_until: Option<Instant> = None;
+    loop {
+        // 命令表只在等待唤醒时更新
+        if listening_until.is_none() && command::take_updated() {
+            commands = command::list();
+            if let Some(multinet) = multinet.as_mut() {
+                if let Err(e) = multinet.set_commands(&commands) {
+                    log::warn!("update commands error: {:?}", e);
+                }
+            }
+        }
+
+        let Some(result) = afe.fetch() else {
+            continue;
+        };
+
+        let Some(until) = listening_until else {
+            if let Some(word) = result.wake_word {
+                on_wake(word, &ui_tx);
+                afe.set_wakenet(false);
+                if let Some(multinet) = multinet.as_mut() {
+                    multinet.clean();
+                }
+                listening_until =
+                    Some(Instant::now() + Duration::from_millis(global::WAKE_LISTEN_MS));
+            }
+            continue;
+        };
+
+        let detect = match multinet.as_mut() {
+            Some(multinet) => multinet.detect(result.data),
+            None => Detect::Detecting,
+        };
+        let done = match detect {
+            Detect::Command(index) => {
+                if let Some(command) = commands.get(index) {
+                    on_command(command);
+                }
+                true
+            }
+            Detect::Timeout => true,
+            Detect::Detecting => Instant::now() >= until,
+        };
+
+        // 聆听结束，恢复待机显示
+        if done {
+            listening_until = None;
+            afe.set_wakenet(true);
+            _ = ui_tx.send(global::UI_TEXT_IDLE.to_string());
+        }
+    }
+}
+
+fn on_wake(word: i32, ui_tx: &mpsc::Sender<String>) {
+    event::emit(Event::Wake { word });
+    // 只打断正在进行的播报，不清空队列
+    job::stop();
+    _ = ui_tx.send(global::UI_TEXT_LISTENING.to_string());
+
+    // 提示音不参与 "重复一遍"
+    let ack = if clip::exists(global::WAKE_ACK_CLIP) {
+        job::Job::clip(global::WAKE_ACK_CLIP.to_string())
+    } else {
+        job::Job::speak(global::WAKE_ACK_TEXT.to_string())
+    };
+    if let Err(e) = job::enqueue_prompt(ack) {
+        log::warn!("enqueue wake ack error: {:?}", e);
+    }
+}
+
+fn on_command(command: &Command) {
+    event::emit(Event::Command {
+        phrase: command.phrase.clone(),
+    });
+    command.action.run();
+}
@@ -1,16 +1,51 @@
+use std::net::Ipv4Addr;
+use std::sync::Mutex;
+
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
     hal::peripheral,
-    wifi::{AccessPointConfiguration, BlockingWifi, Configuration, EspWifi},
+    wifi::{AccessPointConfiguration, BlockingWifi, Configuration, EspWifi, WifiEvent},
 };
 
+use serde::Serialize;
+
+use crate::event::{self, Event};
 use crate::global;
 
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub mode: &'static str,
+    pub ssid: &'static str,
+    pub ip: Option<Ipv4Addr>,
+    // 已连接到 AP 的客户端数量
+    pub clients: usize,
+}
+
+// AP 启动后的 IP 地址
+static AP_IP: Mutex<Option<Ipv4Addr>> = Mutex::new(None);
+
 pub fn wifi_ap(
     modem: impl peripheral::Peripheral<P = esp_idf_svc::hal::modem::Modem> + 'static,
     sysloop: EspSystemEventLoop,
 ) -> anyhow::Result<Box<EspWifi<'static>>> {
     let mut esp_wifi = EspWifi::new(modem, sysloop.clone(), None)?;
+
+    // AP 启停与客户端连接 / 断开时发出 Wifi 事件
+    let subscription = sysloop.subscribe::<WifiEvent, _>(|e| {
+        let change = match e {
+            WifiEvent::ApStarted => "ap_started",
+            WifiEvent::ApStopped => "ap_stopped",
+            WifiEvent::ApStaConnected(_) => "client_connected",
+            WifiEvent::ApStaDisconnected(_) => "client_disconnected",
+            _ => return,
+        };
+        event::emit(Event::Wifi {
+            change,
+            clients: clients(),
+        });
+    })?;
+    core::mem::forget(subscription);
+
     let mut wifi = BlockingWifi::wrap(&mut esp_wifi, sysloop)?;
     let mut wifi_ap_conf = AccessPointConfiguration::default();
     let mut tap_name = wifi_ap_conf.ssid;
@@ -29,6 +64,41 @@ pub fn wifi_ap(
     log::info!("Wifi AP MAC: {:?}", ap_mac);
     let ip_info = wifi.wifi().ap_netif().get_ip_info()?;
     log::info!("Wifi IP info: {:?}", ip_info);
+    *AP_IP.lock().unwrap() = Some(ip_info.ip);
 
     Ok(Box::new(esp_wifi))
 }
+
+pub fn status() -> Status {
+    let ip = *AP_IP.lock().unwrap();
+    Status {
+        mode: if ip.is_some() { "ap" } else { "off" },
+        ssid: global::WIFI_AP_NAME,
+        ip,
+        clients: clients(),
+    }
+}
+
+fn clients() -> usize {
+    sta_list().map(|list| list.num as usize).unwrap_or(0)
+}
+
+// AP 模式下已连接客户端中最强的信号 (dBm)，没有客户端时为 None
+pub fn rssi() -> Option<i8> {
+    let list = sta_list()?;
+    list.sta[..list.num as usize]
+        .iter()
+        .map(|sta| sta.rssi)
+        .max()
+}
+
+fn sta_list() -> Option<esp_idf_svc::sys::wifi_sta_list_t> {
+    unsafe {
+        let mut list: esp_idf_svc::sys::wifi_sta_list_t = std::mem::zeroed();
+        if esp_idf_svc::sys::esp_wifi_ap_get_sta_list(&mut list) == esp_idf_svc::sys::ESP_OK {
+            Some(list)
+        } else {
+            None
+        }
+    }
+}
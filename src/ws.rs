@@ -0,0 +1,128 @@
+// WebSocket 客户端列表，设备事件推送给所有已认证的客户端
+
+use std::sync::Mutex;
+use std::thread;
+
+use esp_idf_svc::http::server::ws::EspHttpWsDetachedSender;
+use esp_idf_svc::ws::FrameType;
+
+use crate::access;
+use crate::event;
+use crate::protocol::{self, Session};
+
+struct Client {
+    session: i32,
+    sender: EspHttpWsDetachedSender,
+    // 未认证时 scope 为 None，不推送事件
+    auth: Session,
+    // 上次校验时的凭据版本
+    revision: u32,
+}
+
+static CLIENTS: Mutex<Vec<Client>> = Mutex::new(Vec::new());
+
+pub fn add(session: i32, sender: EspHttpWsDetachedSender, auth: Session) {
+    log::info!("ws client connected: {}", session);
+    CLIENTS.lock().unwrap().push(Client {
+        session,
+        sender,
+        auth,
+        revision: access::revision(),
+    });
+}
+
+pub fn remove(session: i32) {
+    log::info!("ws client closed: {}", session);
+    CLIENTS.lock().unwrap().retain(|c| c.session != session);
+}
+
+// 返回连接当前的认证状态，凭据修改过时先重新校验
+pub fn auth(session: i32) -> Session {
+    let Some((auth, revision)) = CLIENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|c| c.session == session)
+        .map(|c| (c.auth.clone(), c.revision))
+    else {
+        return Session::default();
+    };
+    refresh(session, auth, revision)
+}
+
+// 凭据修改后重新校验，被删除或降级的凭据立即失去权限
+// PBKDF2 校验较慢，在锁外进行，期间连接的认证状态没有被修改时才写回
+fn refresh(session: i32, mut auth: Session, revision: u32) -> Session {
+    let current = access::revision();
+    if revision == current {
+        return auth;
+    }
+    auth.refresh(access::authenticate);
+    if let Some(client) = CLIENTS
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|c| c.session == session)
+    {
+        if client.revision == revision && client.auth.authorization == auth.authorization {
+            client.auth = auth.clone();
+            client.revision = current;
+        }
+    }
+    auth
+}
+
+// revision 为执行消息前读取的凭据版本，期间凭据被修改时下次使用前会重新校验
+pub fn set_auth(session: i32, auth: Session, revision: u32) {
+    if let Some(client) = CLIENTS
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .find(|c| c.session == session)
+    {
+        client.auth = auth;
+        client.revision = revision;
+    }
+}
+
+// 订阅事件并转发，发送失败的客户端被移除
+pub fn start() -> anyhow::Result<()> {
+    let rx = event::subscribe();
+    thread::Builder::new()
+        .name("ws_events".to_string())
+        .spawn(move || {
+            for event in rx {
+                broadcast(&protocol::encode_event(&event));
+            }
+        })?;
+    Ok(())
+}
+
+// 发送在锁外进行，避免慢速客户端阻塞 auth / set_auth
+fn broadcast(text: &str) {
+    let clients: Vec<_> = {
+        let mut clients = CLIENTS.lock().unwrap();
+        clients.retain(|c| !c.sender.is_closed());
+        clients
+            .iter()
+            .map(|c| (c.session, c.sender.clone(), c.auth.clone(), c.revision))
+            .collect()
+    };
+
+    let mut failed = Vec::new();
+    for (session, mut sender, auth, revision) in clients {
+        if refresh(session, auth, revision).scope.is_none() {
+            continue;
+        }
+        if let Err(e) = sender.send(FrameType::Text(false), text.as_bytes()) {
+            log::warn!("ws send to {} error: {:?}", session, e);
+            failed.push(session);
+        }
+    }
+    if !failed.is_empty() {
+        CLIENTS
+            .lock()
+            .unwrap()
+            .retain(|c| !failed.contains(&c.session));
+    }
+}